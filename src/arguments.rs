@@ -1,41 +1,181 @@
+use std::{env, error, fmt};
 use std::path::{Path};
 
+use crate::settings::{self, Settings, SettingsError};
+
 pub enum AppMode<'a> {
     SingleId(String),
+    SingleVersion(String),
     IdFromFile(&'a Path),
+    ImportPack(&'a Path),
+    Update,
+    Outdated,
+    Scan,
+    ImportDir(&'a Path),
+    Locked,
     ClearMods,
+    Rollback,
     Help
 }
 
+impl AppMode<'_> {
+    /// The argument that selects this mode, for error messages
+    pub fn arg_name(&self) -> &'static str {
+        match self {
+            Self::SingleId(_) => "-id",
+            Self::SingleVersion(_) => "-version",
+            Self::IdFromFile(_) => "--readfile",
+            Self::ImportPack(_) => "--mrpack",
+            Self::Update => "update",
+            Self::Outdated => "outdated",
+            Self::Scan => "scanmods",
+            Self::ImportDir(_) => "import-dir",
+            Self::Locked => "--locked",
+            Self::ClearMods => "clearmods",
+            Self::Rollback => "rollback",
+            Self::Help => "--help"
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ArgError {
+    Invalid(&'static str),
+    LockedWith(&'static str),
+}
+
+impl fmt::Display for ArgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Invalid(msg) => write!(f, "[ARGS/ERROR] {}", msg),
+            Self::LockedWith(arg) => write!(f, "[ARGS/ERROR] --locked installs from the lockfile and can't be used with {}", arg),
+        }
+    }
+}
+
+impl error::Error for ArgError {}
+
+impl From<&'static str> for ArgError {
+    fn from(value: &'static str) -> Self {
+        Self::Invalid(value)
+    }
+}
+
 pub enum Loader {
     Fabric,
     Neoforge,
     Forge
 }
 
+/// Which optional dependencies to install alongside the required ones
+pub enum OptionalDeps {
+    None,
+    All,
+    Projects(Vec<String>),
+}
+
+impl OptionalDeps {
+    pub fn includes(&self, project_id: &Option<String>) -> bool {
+        match (self, project_id) {
+            (Self::All, _) => true,
+            (Self::Projects(ids), Some(id)) => ids.contains(id),
+            _ => false
+        }
+    }
+}
+
+/// Print the dependency graph instead of installing
+pub enum Report<'a> {
+    Tree,
+    Why(&'a str),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Side {
+    Client,
+    Server
+}
+
+/// Extra attempts a download gets after a failed request or hash mismatch
+pub const DEFAULT_RETRIES: u32 = 2;
+
 pub struct Options {
     verify: bool,
     skip_deps: bool,
+    bundle_config: bool,
+    json: bool,
+    allow_incompatible: bool,
+    stats: bool,
+    prefer_newest: bool,
+    retries: u32,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Options {
     pub fn new() -> Self {
         let verify = false;
         let skip_deps = false;
-        Options {verify, skip_deps}
+        let bundle_config = false;
+        let json = false;
+        let allow_incompatible = false;
+        let stats = false;
+        let prefer_newest = false;
+        let retries = DEFAULT_RETRIES;
+        Options {verify, skip_deps, bundle_config, json, allow_incompatible, stats, prefer_newest, retries}
     }
-    pub fn set_verify(&mut self, new:bool) -> () {
+    pub fn set_verify(&mut self, new:bool) {
         self.verify = new;
     }
-    pub fn set_skip_deps(&mut self, new:bool) -> () {
+    pub fn set_skip_deps(&mut self, new:bool) {
         self.skip_deps = new;
     }
+    pub fn set_bundle_config(&mut self, new:bool) {
+        self.bundle_config = new;
+    }
+    pub fn set_json(&mut self, new:bool) {
+        self.json = new;
+    }
+    pub fn set_allow_incompatible(&mut self, new:bool) {
+        self.allow_incompatible = new;
+    }
+    pub fn set_stats(&mut self, new:bool) {
+        self.stats = new;
+    }
+    pub fn set_prefer_newest(&mut self, new:bool) {
+        self.prefer_newest = new;
+    }
+    pub fn set_retries(&mut self, new:u32) {
+        self.retries = new;
+    }
     pub fn get_verify(&self) -> bool {
         self.verify
     }
     pub fn get_skip_deps(&self) -> bool {
         self.skip_deps
     }
+    pub fn get_bundle_config(&self) -> bool {
+        self.bundle_config
+    }
+    pub fn get_json(&self) -> bool {
+        self.json
+    }
+    pub fn get_allow_incompatible(&self) -> bool {
+        self.allow_incompatible
+    }
+    pub fn get_stats(&self) -> bool {
+        self.stats
+    }
+    pub fn get_prefer_newest(&self) -> bool {
+        self.prefer_newest
+    }
+    pub fn get_retries(&self) -> u32 {
+        self.retries
+    }
 }
 
 pub struct Config<'a> {
@@ -43,41 +183,137 @@ pub struct Config<'a> {
     ops: Options,
     mcvs: String,
     loader: Loader,
+    side: Side,
+    optional: OptionalDeps,
+    report: Option<Report<'a>>,
     out_dir: Option<&'a Path>,
+    export_path: Option<&'a Path>,
+    archive_dir: Option<&'a Path>,
+    loader_version: Option<&'a str>,
+    version: Option<&'a str>,
+    curse_key: Option<&'a str>,
+    modrinth_url: Option<&'a str>,
+    settings_path: Option<&'a Path>,
+    settings: Settings,
 }
 
 impl<'a> Config<'a> {
-    pub fn build_from_args(args: &'a Vec<String>) -> Result<Config<'a>, &'static str> {
+    pub fn build_from_args(args: &'a [String]) -> Result<Config<'a>, ArgError> {
         let mut mode: Result<AppMode, &'static str> = Err("No ID specified");
+        let mut locked = false;
         let mut ops: Options = Options::new();
         let mut mcvs: Result<String, &'static str> = Err("No mc version specified");
         let mut loader: Loader = Loader::Fabric;
+        let mut side: Side = Side::Client;
+        let mut optional: OptionalDeps = OptionalDeps::None;
+        let mut report: Option<Report> = None;
         let mut out_dir: Option<&Path> = None;
+        let mut export_path: Option<&Path> = None;
+        let mut archive_dir: Option<&Path> = None;
+        let mut loader_version: Option<&str> = None;
+        let mut version: Option<&str> = None;
+        let mut curse_key: Option<&str> = None;
+        let mut modrinth_url: Option<&str> = None;
+        let mut settings_path: Option<&Path> = None;
         let mut args_iter = args.iter();
         args_iter.next();
         while let Some(arg) = args_iter.next(){
             match arg.as_str() {
                 "-id" => mode = Ok(get_id_mode(args_iter.next())?),
                 "--readfile" => mode = Ok(get_file_mode(args_iter.next())?),
+                "-version" => version = Some(get_version(args_iter.next())?),
                 "-mcv" => mcvs = Ok(get_mcvs(args_iter.next())?),
                 "-l" => loader = get_loader(args_iter.next())?,
+                "--optional" => optional = get_optional(args_iter.next())?,
+                "--side" => side = get_side(args_iter.next())?,
+                "--mrpack" => mode = Ok(get_pack_mode(args_iter.next())?),
                 "-o" => out_dir = Some(get_out_dir(args_iter.next())?),
+                "--export" => export_path = Some(get_export_path(args_iter.next())?),
+                "--loader-version" => loader_version = Some(get_loader_version(args_iter.next())?),
+                "--with-config" => { ops.set_bundle_config(true); },
+                "--cursekey" => curse_key = Some(get_curse_key(args_iter.next())?),
+                "--modrinth-url" => modrinth_url = Some(get_url(args_iter.next())?),
+                "--config" => settings_path = Some(get_settings_path(args_iter.next())?),
+                "--locked" => locked = true,
                 "clearmods" => mode = Ok(AppMode::ClearMods),
+                "rollback" => mode = Ok(AppMode::Rollback),
+                "update" => mode = Ok(AppMode::Update),
+                "outdated" => mode = Ok(AppMode::Outdated),
+                "scanmods" => mode = Ok(AppMode::Scan),
+                "import-dir" => mode = Ok(get_import_dir_mode(args_iter.next())?),
+                "tree" => report = Some(Report::Tree),
+                "why" => report = Some(get_why_report(args_iter.next())?),
+                "--json" => { ops.set_json(true); },
+                "--archive" => archive_dir = Some(get_archive_dir(args_iter.next())?),
                 "checkmods" => { ops.set_verify(true); },
                 "--skipdeps" => { ops.set_skip_deps(true); }
+                "--allow-incompatible" => { ops.set_allow_incompatible(true); }
+                "--stats" => { ops.set_stats(true); }
+                "--prefer-newest" => { ops.set_prefer_newest(true); }
+                "--retries" => ops.set_retries(get_retries(args_iter.next())?),
                 "-h" => mode = Ok(AppMode::Help),
                 "--help" => mode = Ok(AppMode::Help),
                 "-help" => mode = Ok(AppMode::Help),
                 _ => println!("arg '{arg}' not recognized")
             }
         };
-        let mode = mode?;
+        // --locked installs the lockfile, either on its own or in place of
+        // resolving the --readfile it was written from
+        let mode = match mode {
+            m if !locked => m,
+            Ok(AppMode::Help) => Ok(AppMode::Help),
+            _ if version.is_some() => return Err(ArgError::LockedWith("-version")),
+            Err(_) | Ok(AppMode::IdFromFile(_)) => Ok(AppMode::Locked),
+            Ok(m) => return Err(ArgError::LockedWith(m.arg_name())),
+        };
+        // -version pins the -id project, or names a version on its own
+        let mode = match (mode, version) {
+            (Ok(AppMode::SingleId(id)), _) => AppMode::SingleId(id),
+            (Err(_), Some(v)) => AppMode::SingleVersion(v.to_string()),
+            (Ok(_), Some(_)) => return Err(ArgError::Invalid("-version only works with -id")),
+            (m, None) => m?
+        };
         let mcvs = match mode {
+            AppMode::ImportPack(_) => String::new(),
+            AppMode::Scan => String::new(),
+            AppMode::ImportDir(_) => String::new(),
+            AppMode::Locked => String::new(),
             AppMode::ClearMods => String::new(),
+            AppMode::Rollback => String::new(),
             AppMode::Help => String::new(),
             _ => mcvs?
         };
-        Ok(Config { mode, ops, mcvs, loader, out_dir })
+        if report.is_some() && !matches!(
+            mode,
+            AppMode::SingleId(_) | AppMode::SingleVersion(_) | AppMode::IdFromFile(_)
+        ) {
+            return Err(ArgError::Invalid("tree and why need -id, -version or --readfile"));
+        }
+        if matches!(mode, AppMode::ImportPack(_)) && out_dir.is_none() {
+            return Err(ArgError::Invalid("--mrpack needs -o <instance folder>"));
+        }
+        if export_path.is_some() && loader_version.is_none() {
+            return Err(ArgError::Invalid("--export needs --loader-version"));
+        }
+        let settings = Settings::default();
+        Ok(Config {
+            mode,
+            ops,
+            mcvs,
+            loader,
+            side,
+            optional,
+            report,
+            out_dir,
+            export_path,
+            archive_dir,
+            loader_version,
+            version,
+            curse_key,
+            modrinth_url,
+            settings_path,
+            settings
+        })
     }
     pub fn mode(&self) -> &AppMode<'a> {
         &self.mode
@@ -91,9 +327,51 @@ impl<'a> Config<'a> {
     pub fn loader(&self) -> &Loader {
         &self.loader
     }
+    pub fn side(&self) -> &Side {
+        &self.side
+    }
+    pub fn optional(&self) -> &OptionalDeps {
+        &self.optional
+    }
+    pub fn report(&self) -> &Option<Report<'a>> {
+        &self.report
+    }
     pub fn out_dir(&self) -> &Option<&Path> {
         &self.out_dir
     }
+    pub fn export_path(&self) -> &Option<&'a Path> {
+        &self.export_path
+    }
+    pub fn archive_dir(&self) -> &Option<&'a Path> {
+        &self.archive_dir
+    }
+    pub fn loader_version(&self) -> &Option<&'a str> {
+        &self.loader_version
+    }
+    pub fn version(&self) -> &Option<&'a str> {
+        &self.version
+    }
+    pub fn curse_key(&self) -> &Option<&'a str> {
+        &self.curse_key
+    }
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+    /// Reads the config file named by `--config`, or the default one if present
+    pub fn load_settings(&mut self) -> Result<(), SettingsError> {
+        self.settings = Settings::load(self.settings_path)?;
+        Ok(())
+    }
+    /// Modrinth base URL from the CLI, then the environment, then the config file
+    pub fn modrinth_url(&self) -> Option<String> {
+        if let Some(url) = self.modrinth_url {
+            Some(url.to_string())
+        } else if let Ok(url) = env::var(settings::MODRINTH_URL_VAR) {
+            Some(url)
+        } else {
+            self.settings.modrinth_url().clone()
+        }
+    }
     pub fn loader_as_str(&self) -> &str {
         match self.loader {
             Loader::Fabric => "fabric",
@@ -117,6 +395,13 @@ fn get_mcvs(mcvs: Option<&String>) -> Result<String, &'static str> {
     }
 }
 
+fn get_retries(retries: Option<&String>) -> Result<u32, &'static str> {
+    match retries.map(|r| r.parse::<u32>()) {
+        Some(Ok(n)) => Ok(n),
+        _ => Err("Invalid retry count")
+    }
+}
+
 fn get_loader(loader: Option<&String>) -> Result<Loader, &'static str> {
     match loader {
         Some(v) => { match v.as_str() {
@@ -129,6 +414,32 @@ fn get_loader(loader: Option<&String>) -> Result<Loader, &'static str> {
     }
 }
 
+fn get_optional(ids: Option<&String>) -> Result<OptionalDeps, &'static str> {
+    match ids.map(String::as_str) {
+        Some("all") => Ok(OptionalDeps::All),
+        Some("") | None => Err("Invalid optional dependencies"),
+        Some(v) => Ok(OptionalDeps::Projects(v.split(',').map(String::from).collect()))
+    }
+}
+
+fn get_side(side: Option<&String>) -> Result<Side, &'static str> {
+    match side {
+        Some(v) => { match v.as_str() {
+            "client" => Ok(Side::Client),
+            "server" => Ok(Side::Server),
+            _ => Err("Invalid side")
+        }},
+        None => Err("Invalid side")
+    }
+}
+
+fn get_version(version: Option<&String>) -> Result<&str, &'static str> {
+    match version {
+        Some(v) => Ok(v.as_str()),
+        None => Err("Invalid version")
+    }
+}
+
 fn get_id_mode<'a>(id: Option<&'a String>) -> Result<AppMode<'a>, &'static str> {
     match id {
         Some(v) => Ok(AppMode::SingleId(v.to_string())),
@@ -138,14 +449,77 @@ fn get_id_mode<'a>(id: Option<&'a String>) -> Result<AppMode<'a>, &'static str>
 
 fn get_file_mode<'a>(file: Option<&'a String>) -> Result<AppMode<'a>, &'static str> {
     match file {
-        Some(v) => Ok(AppMode::IdFromFile(&Path::new(v))),
+        Some(v) => Ok(AppMode::IdFromFile(Path::new(v))),
         None => Err("Invalid filename")
     }
 }
 
+fn get_pack_mode<'a>(file: Option<&'a String>) -> Result<AppMode<'a>, &'static str> {
+    match file {
+        Some(v) => Ok(AppMode::ImportPack(Path::new(v))),
+        None => Err("Invalid modpack file")
+    }
+}
+
+fn get_import_dir_mode<'a>(file: Option<&'a String>) -> Result<AppMode<'a>, &'static str> {
+    match file {
+        Some(v) => Ok(AppMode::ImportDir(Path::new(v))),
+        None => Err("Invalid manifest filename")
+    }
+}
+
+fn get_why_report<'a>(project: Option<&'a String>) -> Result<Report<'a>, &'static str> {
+    match project {
+        Some(p) => Ok(Report::Why(p.as_str())),
+        None => Err("Invalid project for why")
+    }
+}
+
 fn get_out_dir(file: Option<&String>) -> Result<&Path, &'static str> {
     match file {
         Some(f) => Ok(Path::new(f)),
         None => Err("Invalid output directory")
     }
+}
+
+fn get_export_path(file: Option<&String>) -> Result<&Path, &'static str> {
+    match file {
+        Some(f) => Ok(Path::new(f)),
+        None => Err("Invalid export file")
+    }
+}
+
+fn get_archive_dir(dir: Option<&String>) -> Result<&Path, &'static str> {
+    match dir {
+        Some(d) => Ok(Path::new(d)),
+        None => Err("Invalid archive folder")
+    }
+}
+
+fn get_loader_version(version: Option<&String>) -> Result<&str, &'static str> {
+    match version {
+        Some(v) => Ok(v.as_str()),
+        None => Err("Invalid loader version")
+    }
+}
+
+fn get_curse_key(key: Option<&String>) -> Result<&str, &'static str> {
+    match key {
+        Some(k) => Ok(k.as_str()),
+        None => Err("Invalid CurseForge API key")
+    }
+}
+
+fn get_url(url: Option<&String>) -> Result<&str, &'static str> {
+    match url {
+        Some(u) => Ok(u.as_str()),
+        None => Err("Invalid URL")
+    }
+}
+
+fn get_settings_path(file: Option<&String>) -> Result<&Path, &'static str> {
+    match file {
+        Some(f) => Ok(Path::new(f)),
+        None => Err("Invalid config file")
+    }
 }
\ No newline at end of file
@@ -0,0 +1,257 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::sync::OnceCell;
+
+use crate::provider::{
+    DependencyInfo,
+    FileInfo,
+    ModError,
+    ModProvider,
+    ProjectInfo,
+    VersionInfo,
+    VersionQuery
+};
+
+/// Results by key. Callers asking for a key that is still being fetched
+/// wait on the same cell instead of sending their own request. Failures
+/// aren't stored, so the next caller tries again.
+type Memo<T> = Mutex<HashMap<String, Arc<OnceCell<T>>>>;
+
+/// How often one kind of call reached the provider or came from the cache
+#[derive(Default)]
+pub struct CallStats {
+    requests: AtomicUsize,
+    cached: AtomicUsize,
+}
+
+impl CallStats {
+    pub fn requests(&self) -> usize {
+        self.requests.load(Ordering::Relaxed)
+    }
+    pub fn cached(&self) -> usize {
+        self.cached.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Default)]
+pub struct RequestStats {
+    projects: CallStats,
+    version_lists: CallStats,
+    versions: CallStats,
+    dependencies: CallStats,
+}
+
+impl RequestStats {
+    pub fn projects(&self) -> &CallStats {
+        &self.projects
+    }
+    pub fn version_lists(&self) -> &CallStats {
+        &self.version_lists
+    }
+    pub fn versions(&self) -> &CallStats {
+        &self.versions
+    }
+    pub fn dependencies(&self) -> &CallStats {
+        &self.dependencies
+    }
+    fn all(&self) -> [&CallStats; 4] {
+        [&self.projects, &self.version_lists, &self.versions, &self.dependencies]
+    }
+    pub fn requests(&self) -> usize {
+        self.all().iter().map(|s| s.requests()).sum()
+    }
+    pub fn cached(&self) -> usize {
+        self.all().iter().map(|s| s.cached()).sum()
+    }
+    pub fn summary(&self) -> String {
+        format!(
+            "{} requests ({} projects, {} version lists, {} versions, {} dependencies), {} served from cache",
+            self.requests(),
+            self.projects.requests(),
+            self.version_lists.requests(),
+            self.versions.requests(),
+            self.dependencies.requests(),
+            self.cached()
+        )
+    }
+}
+
+/// Wraps a provider for one resolution run, so every project, version list,
+/// version and dependency is fetched at most once
+pub struct CachedProvider<'p, P: ModProvider> {
+    inner: &'p P,
+    projects: Memo<ProjectInfo>,
+    version_lists: Memo<Vec<VersionInfo>>,
+    versions: Memo<VersionInfo>,
+    dependencies: Memo<VersionInfo>,
+    stats: RequestStats,
+}
+
+impl<'p, P: ModProvider> CachedProvider<'p, P> {
+    pub fn new(inner: &'p P) -> Self {
+        CachedProvider {
+            inner,
+            projects: Memo::default(),
+            version_lists: Memo::default(),
+            versions: Memo::default(),
+            dependencies: Memo::default(),
+            stats: RequestStats::default()
+        }
+    }
+    pub fn stats(&self) -> &RequestStats {
+        &self.stats
+    }
+}
+
+async fn memoize<T, F>(
+    memo: &Memo<T>,
+    key: String,
+    stats: &CallStats,
+    fetch: impl FnOnce() -> F
+) -> Result<T, ModError>
+where
+    T: Clone,
+    F: Future<Output = Result<T, ModError>>
+{
+    let cell = memo.lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .entry(key)
+        .or_default()
+        .clone();
+    let mut fetched = false;
+    let value = cell.get_or_try_init(|| {
+        fetched = true;
+        fetch()
+    }).await?;
+    if fetched {
+        stats.requests.fetch_add(1, Ordering::Relaxed);
+    } else {
+        stats.cached.fetch_add(1, Ordering::Relaxed);
+    }
+    Ok(value.clone())
+}
+
+fn is_cached<T>(memo: &Memo<T>, key: &str) -> bool {
+    memo.lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(key)
+        .is_some_and(|cell| cell.initialized())
+}
+
+/// Stores `value` under `key` unless another caller got there first
+fn seed<T>(memo: &Memo<T>, key: String, value: T) {
+    let cell = memo.lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .entry(key)
+        .or_default()
+        .clone();
+    let _ = cell.set(value);
+}
+
+fn query_key(query: &VersionQuery) -> String {
+    format!("{}|{}", query.mcvs(), query.loader())
+}
+
+fn version_key(project_id: Option<&str>, version_id: &str) -> String {
+    format!("{}|{}", project_id.unwrap_or_default(), version_id)
+}
+
+impl<P: ModProvider> ModProvider for CachedProvider<'_, P> {
+    fn tag(&self) -> &'static str {
+        self.inner.tag()
+    }
+    async fn resolve_project(&self, id: &str) -> Result<ProjectInfo, ModError> {
+        memoize(&self.projects, id.to_string(), &self.stats.projects, || {
+            self.inner.resolve_project(id)
+        }).await
+    }
+    /// Fetches the uncached `ids` in bulk, then serves every ID
+    /// from the cache. IDs the bulk call didn't return, such as slugs, fall
+    /// back to single requests.
+    async fn resolve_projects(&self, ids: &[String]) -> Result<Vec<ProjectInfo>, ModError> {
+        let missing: Vec<String> = ids.iter()
+            .filter(|id| !is_cached(&self.projects, id))
+            .cloned()
+            .collect();
+        if !missing.is_empty() {
+            let fetched = self.inner.resolve_projects(&missing).await?;
+            self.stats.projects.requests.fetch_add(self.inner.bulk_requests(missing.len()), Ordering::Relaxed);
+            for p in fetched {
+                seed(&self.projects, p.get_id().clone(), p);
+            }
+        }
+        let mut projects: Vec<ProjectInfo> = Vec::new();
+        for id in ids {
+            if let Ok(p) = self.resolve_project(id).await {
+                projects.push(p);
+            }
+        }
+        Ok(projects)
+    }
+    async fn get_versions(&self, version_ids: &[String]) -> Result<Vec<VersionInfo>, ModError> {
+        let missing: Vec<String> = version_ids.iter()
+            .filter(|id| !is_cached(&self.versions, &version_key(None, id)))
+            .cloned()
+            .collect();
+        if !missing.is_empty() {
+            let fetched = self.inner.get_versions(&missing).await?;
+            self.stats.versions.requests.fetch_add(self.inner.bulk_requests(missing.len()), Ordering::Relaxed);
+            for v in fetched {
+                seed(&self.versions, version_key(None, v.id()), v);
+            }
+        }
+        let mut versions: Vec<VersionInfo> = Vec::new();
+        for id in version_ids {
+            if let Ok(v) = self.get_version(None, id).await {
+                versions.push(v);
+            }
+        }
+        Ok(versions)
+    }
+    async fn list_versions(
+        &self,
+        project_id: &str,
+        query: &VersionQuery
+    ) -> Result<Vec<VersionInfo>, ModError> {
+        let key = format!("{}|{}", project_id, query_key(query));
+        memoize(&self.version_lists, key, &self.stats.version_lists, || {
+            self.inner.list_versions(project_id, query)
+        }).await
+    }
+    async fn get_version(
+        &self,
+        project_id: Option<&str>,
+        version_id: &str
+    ) -> Result<VersionInfo, ModError> {
+        let key = version_key(project_id, version_id);
+        memoize(&self.versions, key, &self.stats.versions, || {
+            self.inner.get_version(project_id, version_id)
+        }).await
+    }
+    async fn resolve_dependency(
+        &self,
+        dep: &DependencyInfo,
+        query: &VersionQuery
+    ) -> Result<VersionInfo, ModError> {
+        // A pinned dependency is just that version, which may already be
+        // cached from a bulk lookup
+        if let Some(version_id) = dep.version_id() {
+            return self.get_version(None, version_id).await;
+        }
+        let key = format!(
+            "{}|{}",
+            dep.project_id().as_deref().unwrap_or_default(),
+            query_key(query)
+        );
+        memoize(&self.dependencies, key, &self.stats.dependencies, || {
+            self.inner.resolve_dependency(dep, query)
+        }).await
+    }
+    fn bulk_requests(&self, count: usize) -> usize {
+        self.inner.bulk_requests(count)
+    }
+    fn file_info(&self, version: &VersionInfo) -> Option<FileInfo> {
+        self.inner.file_info(version)
+    }
+}
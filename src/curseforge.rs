@@ -0,0 +1,354 @@
+use std::collections::HashSet;
+use std::env;
+use serde::{Deserialize, Deserializer};
+use serde::de::DeserializeOwned;
+
+use crate::arguments;
+use crate::http::Http;
+use crate::provider::{
+    DependencyInfo,
+    DependencyKind,
+    FileHashes,
+    FileInfo,
+    ModError,
+    ModProvider,
+    ProjectInfo,
+    VersionInfo,
+    VersionQuery
+};
+
+static CURSEFORGE_URL: &str = "https://api.curseforge.com";
+pub const API_KEY_VAR: &str = "CURSEFORGE_API_KEY";
+const HASH_ALGO_SHA1: u8 = 1;
+const RELATION_EMBEDDED: u8 = 1;
+const RELATION_OPTIONAL: u8 = 2;
+const RELATION_REQUIRED: u8 = 3;
+const RELATION_INCOMPATIBLE: u8 = 5;
+const RELEASE_TYPES: [&str; 3] = ["release", "beta", "alpha"];
+/// Largest page the files endpoint returns
+const PAGE_SIZE: usize = 50;
+
+pub struct CurseForge {
+    http: Http,
+    base_url: String,
+    api_key: String,
+}
+
+impl CurseForge {
+    pub fn build(client: &reqwest::Client, base_url: &str, api_key: &str) -> Self {
+        CurseForge {
+            http: Http::new(client),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key: api_key.to_string()
+        }
+    }
+    pub fn from_config(
+        conf: &arguments::Config<'_>,
+        client: &reqwest::Client
+    ) -> Result<Self, ModError> {
+        let api_key = match conf.curse_key() {
+            Some(k) => k.to_string(),
+            None => env::var(API_KEY_VAR)
+                .ok()
+                .or(conf.settings().curseforge_api_key().clone())
+                .ok_or(ModError::NoApiKey(
+                    format!("set {} or use --cursekey", API_KEY_VAR)
+                ))?
+        };
+        let base_url = conf.settings()
+            .curseforge_url()
+            .clone()
+            .unwrap_or(CURSEFORGE_URL.to_string());
+        Ok(Self::build(client, &base_url, &api_key))
+    }
+    async fn get<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, &str)]
+    ) -> Result<T, ModError> {
+        Ok(self.get_data::<Data<T>>(path, query).await?.data)
+    }
+    async fn get_data<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, &str)]
+    ) -> Result<T, ModError> {
+        let url = format!("{}{}", self.base_url, path);
+        let request = self.http.client().get(url)
+            .header("x-api-key", &self.api_key)
+            .query(query);
+        let response = self.http.send(path, request).await?;
+        Ok(response.json::<T>().await?)
+    }
+    /// Every page of a paginated list, until `totalCount` is reached
+    async fn get_all<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, &str)]
+    ) -> Result<Vec<T>, ModError> {
+        let mut items: Vec<T> = Vec::new();
+        let page_size = PAGE_SIZE.to_string();
+        loop {
+            let index = items.len().to_string();
+            let mut page_query = query.to_vec();
+            page_query.push(("index", index.as_str()));
+            page_query.push(("pageSize", page_size.as_str()));
+            let page = self.get_data::<Page<T>>(path, &page_query).await?;
+            let received = page.data.len();
+            items.extend(page.data);
+            match page.pagination {
+                Some(p) if received > 0 && items.len() < p.total_count => continue,
+                _ => return Ok(items)
+            }
+        }
+    }
+    pub async fn get_mod(&self, mod_id: u32) -> Result<CurseProject, ModError> {
+        self.get(&format!("/v1/mods/{}", mod_id), &[]).await
+    }
+    pub async fn get_file(&self, mod_id: u32, file_id: u32) -> Result<CurseFile, ModError> {
+        self.get(&format!("/v1/mods/{}/files/{}", mod_id, file_id), &[]).await
+    }
+    /// Files for every game version and loader in `query`, newest first
+    pub async fn get_files(
+        &self,
+        mod_id: u32,
+        query: &VersionQuery
+    ) -> Result<Vec<CurseFile>, ModError> {
+        let mut files: Vec<CurseFile> = Vec::new();
+        let mut seen: HashSet<u32> = HashSet::new();
+        for game_version in query.mcvs_list() {
+            for loader in query.loader_list() {
+                let page = self.get_all::<CurseFile>(
+                    &format!("/v1/mods/{}/files", mod_id),
+                    &[
+                        ("gameVersion", game_version.as_str()),
+                        ("modLoaderType", loader_type(loader))
+                    ]
+                ).await?;
+                files.extend(page.into_iter().filter(|f| seen.insert(f.id)));
+            }
+        }
+        files.sort_by(|a, b| b.file_date.cmp(&a.file_date));
+        Ok(files)
+    }
+    pub async fn get_top_file(
+        &self,
+        mod_id: u32,
+        query: &VersionQuery
+    ) -> Result<CurseFile, ModError> {
+        self.get_files(mod_id, query)
+        .await?
+        .into_iter()
+        .next()
+        .ok_or(ModError::NoVersionForId(mod_id.to_string()))
+    }
+}
+
+fn loader_type(loader: &str) -> &'static str {
+    match loader {
+        "forge" => "1",
+        "fabric" => "4",
+        "quilt" => "5",
+        "neoforge" => "6",
+        _ => "0"
+    }
+}
+
+fn parse_mod_id(id: &str) -> Result<u32, ModError> {
+    id.parse::<u32>().map_err(|_| ModError::BadId(id.to_string()))
+}
+
+impl ModProvider for CurseForge {
+    fn tag(&self) -> &'static str {
+        "CURSEFORGE"
+    }
+    async fn resolve_project(&self, id: &str) -> Result<ProjectInfo, ModError> {
+        let proj = self.get_mod(parse_mod_id(id)?).await?;
+        Ok(ProjectInfo::new(proj.id.to_string(), proj.name))
+    }
+    async fn list_versions(
+        &self,
+        project_id: &str,
+        query: &VersionQuery
+    ) -> Result<Vec<VersionInfo>, ModError> {
+        Ok(self.get_files(parse_mod_id(project_id)?, query)
+            .await?
+            .into_iter()
+            .map(VersionInfo::from)
+            .collect()
+        )
+    }
+    async fn get_version(
+        &self,
+        project_id: Option<&str>,
+        version_id: &str
+    ) -> Result<VersionInfo, ModError> {
+        let mod_id = project_id.ok_or(ModError::NoVersionForId(
+            format!("CurseForge file '{}' needs its mod ID", version_id)
+        ))?;
+        Ok(self.get_file(parse_mod_id(mod_id)?, parse_mod_id(version_id)?).await?.into())
+    }
+    async fn resolve_dependency(
+        &self,
+        dep: &DependencyInfo,
+        query: &VersionQuery
+    ) -> Result<VersionInfo, ModError> {
+        match (dep.project_id(), dep.version_id()) {
+            (Some(p), Some(v)) => self.get_version(Some(p), v).await,
+            (Some(p), None) => Ok(self.get_top_file(parse_mod_id(p)?, query).await?.into()),
+            _ => Err(ModError::NoDependency("Could not resolve dependency".to_string()))
+        }
+    }
+    fn file_info(&self, version: &VersionInfo) -> Option<FileInfo> {
+        version.files().first().cloned()
+    }
+}
+
+#[derive(Deserialize)]
+struct Data<T> {
+    data: T
+}
+
+#[derive(Deserialize)]
+struct Page<T> {
+    data: Vec<T>,
+    pagination: Option<Pagination>
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Pagination {
+    total_count: usize
+}
+
+#[derive(Deserialize)]
+pub struct CurseProject {
+    id: u32,
+    name: String,
+}
+
+impl CurseProject {
+    pub fn get_id(&self) -> u32 {
+        self.id
+    }
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CurseFile {
+    id: u32,
+    mod_id: u32,
+    display_name: String,
+    file_name: String,
+    file_date: String,
+    #[serde(default)]
+    release_type: u8,
+    download_url: Option<String>,
+    #[serde(default)]
+    file_length: Option<u64>,
+    #[serde(deserialize_with = "deserialize_sha1")]
+    hashes: Option<Vec<u8>>,
+    #[serde(deserialize_with = "deserialize_known_deps")]
+    dependencies: Vec<(u32, DependencyKind)>,
+}
+
+impl CurseFile {
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+    pub fn mod_id(&self) -> u32 {
+        self.mod_id
+    }
+    pub fn display_name(&self) -> &String {
+        &self.display_name
+    }
+    pub fn file_name(&self) -> &String {
+        &self.file_name
+    }
+    pub fn download_url(&self) -> &Option<String> {
+        &self.download_url
+    }
+    pub fn dependencies(&self) -> &Vec<(u32, DependencyKind)> {
+        &self.dependencies
+    }
+}
+
+impl From<CurseFile> for VersionInfo {
+    fn from(value: CurseFile) -> Self {
+        // Files with third-party downloads disabled have no URL and so
+        // nothing the pipeline can install
+        let files = match value.download_url {
+            Some(url) => vec![FileInfo::new(
+                url,
+                value.file_name.clone(),
+                true,
+                FileHashes::new(None, value.hashes),
+                value.file_length
+            )],
+            None => Vec::new()
+        };
+        VersionInfo::new(
+            value.id.to_string(),
+            value.mod_id.to_string(),
+            value.display_name,
+            value.file_name,
+            // 1 = release, 2 = beta, 3 = alpha
+            RELEASE_TYPES.get((value.release_type as usize).wrapping_sub(1)).map(|t| t.to_string()),
+            files,
+            value.dependencies.into_iter()
+                .map(|(id, kind)| DependencyInfo::new(None, Some(id.to_string()), kind))
+                .collect()
+        ).with_published(value.file_date)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CurseFileHash {
+    value: String,
+    algo: u8,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CurseDependency {
+    mod_id: u32,
+    relation_type: u8,
+}
+
+fn deserialize_sha1<'de, D>(
+    deserializer: D
+) -> Result<Option<Vec<u8>>, D::Error>
+    where D: Deserializer<'de>
+{
+    let hashes: Vec<CurseFileHash> = Deserialize::deserialize(deserializer)?;
+    Ok(hashes.into_iter()
+        .find(|h| h.algo == HASH_ALGO_SHA1)
+        .and_then(|h| hex::decode(h.value).ok())
+    )
+}
+
+/// Keeps the relations the pipeline understands; tools and includes are dropped
+fn deserialize_known_deps<'de, D>(
+    deserializer: D
+) -> Result<Vec<(u32, DependencyKind)>, D::Error>
+    where D: Deserializer<'de>
+{
+    let deps: Vec<CurseDependency> = Deserialize::deserialize(deserializer)?;
+    Ok(deps.into_iter()
+        .filter_map(|d| {
+            let kind = match d.relation_type {
+                RELATION_EMBEDDED => DependencyKind::Embedded,
+                RELATION_OPTIONAL => DependencyKind::Optional,
+                RELATION_REQUIRED => DependencyKind::Required,
+                RELATION_INCOMPATIBLE => DependencyKind::Incompatible,
+                _ => return None
+            };
+            Some((d.mod_id, kind))
+        })
+        .collect()
+    )
+}
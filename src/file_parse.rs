@@ -1,19 +1,169 @@
 use std::fs::File;
-use std::io::{self, BufRead, BufReader};
+use std::{fmt, io, error};
+use std::io::{BufRead, BufReader};
 use std::path::Path;
 
-pub enum IdType<'a> {
-    Modrinth(&'a str),
-    Curseforge(&'a str),
+use crate::arguments::Side;
+use crate::provider::{VersionInfo, VersionQuery};
+
+const MODRINTH_PROJECT_KINDS: [&str; 7] = [
+    "mod", "plugin", "datapack", "shader", "resourcepack", "modpack", "project"
+];
+
+/// A malformed manifest line, numbered from 1
+#[derive(Debug)]
+pub struct ParseDiagnostic {
+    line: usize,
+    text: String,
+    message: String,
+}
+
+impl ParseDiagnostic {
+    pub fn line(&self) -> usize {
+        self.line
+    }
+    pub fn text(&self) -> &String {
+        &self.text
+    }
+    pub fn message(&self) -> &String {
+        &self.message
+    }
+}
+
+impl fmt::Display for ParseDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {} ('{}')", self.line, self.message, self.text)
+    }
+}
+
+#[derive(Debug)]
+pub enum ManifestError {
+    BadFile(io::Error),
+    BadLines(Vec<ParseDiagnostic>),
+}
+
+impl fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BadFile(err) => write!(f, "[MANIFEST/ERROR] Bad file: {}", err),
+            Self::BadLines(diagnostics) => {
+                write!(f, "[MANIFEST/ERROR] {} malformed line(s)", diagnostics.len())?;
+                for d in diagnostics {
+                    write!(f, "\n  {}", d)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl error::Error for ManifestError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::BadFile(err) => Some(err),
+            _ => None
+        }
+    }
+}
+
+impl From<io::Error> for ManifestError {
+    fn from(value: io::Error) -> Self {
+        Self::BadFile(value)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Source {
+    Modrinth,
+    Curseforge,
+}
+
+/// One manifest line: `<id>[@<version>] [-curse|-modrinth] [key=value ...]`.
+/// Keys are `version`, `version_number`, `types`, `loader`, `side`,
+/// `optional` and `source`.
+#[derive(Clone, Debug)]
+pub struct ManifestEntry {
+    id: String,
+    source: Source,
+    version_id: Option<String>,
+    version_number: Option<String>,
+    version_types: Option<Vec<String>>,
+    loader: Option<String>,
+    side: Option<Side>,
+    with_optional: bool,
+}
+
+impl ManifestEntry {
+    pub fn new(id: String, source: Source) -> Self {
+        ManifestEntry {
+            id,
+            source,
+            version_id: None,
+            version_number: None,
+            version_types: None,
+            loader: None,
+            side: None,
+            with_optional: false
+        }
+    }
+    pub fn id(&self) -> &String {
+        &self.id
+    }
+    pub fn source(&self) -> Source {
+        self.source
+    }
+    pub fn version_id(&self) -> &Option<String> {
+        &self.version_id
+    }
+    pub fn version_number(&self) -> &Option<String> {
+        &self.version_number
+    }
+    pub fn version_types(&self) -> &Option<Vec<String>> {
+        &self.version_types
+    }
+    pub fn loader(&self) -> &Option<String> {
+        &self.loader
+    }
+    pub fn side(&self) -> &Option<Side> {
+        &self.side
+    }
+    /// Whether this mod's optional dependencies are installed too
+    pub fn with_optional(&self) -> bool {
+        self.with_optional
+    }
+    /// Whether this entry is installed on `side`; entries without a side go everywhere
+    pub fn wanted_on(&self, side: &Side) -> bool {
+        self.side.is_none_or(|s| s == *side)
+    }
+    /// `query` with this entry's loader override applied
+    pub fn query(&self, query: &VersionQuery) -> VersionQuery {
+        match &self.loader {
+            Some(loader) => VersionQuery::build_query(&query.mcvs_list().join(","), loader),
+            None => VersionQuery::build_query(
+                &query.mcvs_list().join(","),
+                &query.loader_list().join(",")
+            )
+        }
+    }
+    /// Whether `version` satisfies the version_number pattern and allowed types
+    pub fn accepts(&self, version: &VersionInfo) -> bool {
+        let number_ok = self.version_number.as_ref()
+            .is_none_or(|p| matches_pattern(p, version.version_number()));
+        let type_ok = match (&self.version_types, version.version_type()) {
+            (Some(types), Some(t)) => types.contains(t),
+            _ => true
+        };
+        number_ok && type_ok
+    }
 }
 
 pub struct FileIDs {
-    modrinth: Option<Vec<String>>,
-    curseforge: Option<Vec<String>>,
+    modrinth: Option<Vec<ManifestEntry>>,
+    curseforge: Option<Vec<ManifestEntry>>,
 }
 
 impl FileIDs {
-    pub fn build(modrinth_ids: Vec<String>, curse_ids: Vec<String>) -> FileIDs {
+    pub fn build(modrinth_ids: Vec<ManifestEntry>, curse_ids: Vec<ManifestEntry>) -> FileIDs {
         let modrinth = match modrinth_ids.len() {
             0 => None,
             _ => Some(modrinth_ids)
@@ -25,7 +175,7 @@ impl FileIDs {
         FileIDs { modrinth, curseforge }
     }
 
-    pub fn build_modrinth_only(ids: Vec<String>) -> FileIDs {
+    pub fn build_modrinth_only(ids: Vec<ManifestEntry>) -> FileIDs {
         let modrinth = match ids.len() {
             0 => None,
             _ => Some(ids)
@@ -33,47 +183,212 @@ impl FileIDs {
         let curseforge = None;
         FileIDs { modrinth, curseforge }
     }
-    
-    pub fn modrinth(&self) -> &Option<Vec<String>> {
+
+    pub fn modrinth(&self) -> &Option<Vec<ManifestEntry>> {
         &self.modrinth
     }
 
-    pub fn curseforge(&self) -> &Option<Vec<String>> {
+    pub fn curseforge(&self) -> &Option<Vec<ManifestEntry>> {
         &self.curseforge
     }
 }
 
-pub fn parse_ids(filepath: &Path) -> io::Result<FileIDs> {
-    let mut modrinth_ids: Vec<String> = Vec::new();
-    let mut curse_ids: Vec<String> = Vec::new();
+/// Reads every entry in `filepath`. Malformed lines are all reported
+/// together rather than stopping at the first.
+pub fn parse_ids(filepath: &Path) -> Result<FileIDs, ManifestError> {
+    let mut modrinth_ids: Vec<ManifestEntry> = Vec::new();
+    let mut curse_ids: Vec<ManifestEntry> = Vec::new();
+    let mut diagnostics: Vec<ParseDiagnostic> = Vec::new();
 
     let f_in = File::open(filepath)?;
     let reader = BufReader::new(f_in);
-    for line_res in reader.lines() {
+    for (line_num, line_res) in reader.lines().enumerate() {
         let line = line_res?;
-        if let Some(val) = parse_input_line(&line){
-            match val {
-                IdType::Modrinth(id) => { modrinth_ids.push(String::from(id)); },
-                IdType::Curseforge(id) => { curse_ids.push(String::from(id)); }
-            }
+        match parse_input_line(&line) {
+            Ok(Some(val)) => match val.source {
+                Source::Modrinth => { modrinth_ids.push(val); },
+                Source::Curseforge => { curse_ids.push(val); }
+            },
+            Ok(None) => (),
+            Err(message) => diagnostics.push(ParseDiagnostic {
+                line: line_num + 1,
+                text: line.trim().to_string(),
+                message
+            })
         }
     }
+    if !diagnostics.is_empty() {
+        return Err(ManifestError::BadLines(diagnostics));
+    }
 
     Ok(FileIDs::build(modrinth_ids, curse_ids))
 }
 
-pub fn parse_input_line<'a>(line: &'a String) -> Option<IdType<'a>> {
-    let mut line_iter = line.split(" ");
-    let id: &'a str = match line_iter.next() {
+/// Parses one manifest line. Blank lines and comments (`#` to the end of
+/// the line) give `None`.
+pub fn parse_input_line(line: &str) -> Result<Option<ManifestEntry>, String> {
+    let line = match line.split_once('#') {
+        Some((content, _comment)) => content,
+        None => line
+    };
+    let mut line_iter = line.split_whitespace();
+    let id = match line_iter.next() {
         Some(val) => val,
-        None => { return None; }
+        None => { return Ok(None); }
     };
-    if let Some(val) = line_iter.next() {
-        match val {
-            "-curse" => Some(IdType::Curseforge(id)),
-            _ => Some(IdType::Modrinth(id))
+    let (id, at_version) = split_pin(id);
+    let mut entry = ManifestEntry::new(id.to_string(), Source::Modrinth);
+    entry.version_id = at_version;
+    let mut source: Option<Source> = None;
+    for token in line_iter {
+        let (key, value) = match token {
+            "-curse" => ("source", "curseforge"),
+            "-modrinth" => ("source", "modrinth"),
+            _ => token.split_once('=')
+                .ok_or(format!("Unexpected '{}'; expected -curse, -modrinth or key=value", token))?
+        };
+        if value.is_empty() {
+            return Err(format!("No value for '{}'", key));
+        }
+        match key {
+            "source" => {
+                let new = get_source(value)?;
+                if source.is_some_and(|s| s != new) {
+                    return Err(String::from("Conflicting sources"));
+                }
+                source = Some(new);
+            },
+            "version" => {
+                if entry.version_id.as_ref().is_some_and(|pinned| pinned != value) {
+                    return Err(String::from("Conflicting versions"));
+                }
+                entry.version_id = Some(value.to_string());
+            },
+            "version_number" => entry.version_number = Some(value.to_string()),
+            "types" => entry.version_types = Some(get_version_types(value)?),
+            "loader" => entry.loader = Some(value.to_string()),
+            "side" => entry.side = Some(get_side(value)?),
+            "optional" => entry.with_optional = get_bool(value)?,
+            _ => return Err(format!("Unknown key '{}'", key))
         }
-    } else {
-        Some(IdType::Modrinth(id))
     }
-}
\ No newline at end of file
+    entry.source = source.unwrap_or(Source::Modrinth);
+    if entry.source == Source::Modrinth {
+        let (project, version) = normalize_modrinth_id(&entry.id)?;
+        if let Some(v) = version {
+            if entry.version_id.as_ref().is_some_and(|pinned| *pinned != v) {
+                return Err(String::from("Conflicting versions"));
+            }
+            entry.version_id = Some(v);
+        }
+        entry.id = project;
+    }
+    Ok(Some(entry))
+}
+
+/// Manifest entry for a single Modrinth ID, slug or URL given on the
+/// command line, optionally pinned with `@<version>` or `pinned_version`
+pub fn entry_from_id(id: &str, pinned_version: Option<&str>) -> Result<ManifestEntry, String> {
+    let (id, at_version) = split_pin(id);
+    let (project, url_version) = normalize_modrinth_id(id)?;
+    let mut versions: Vec<String> = [at_version, url_version, pinned_version.map(String::from)]
+        .into_iter()
+        .flatten()
+        .collect();
+    versions.dedup();
+    if versions.len() > 1 {
+        return Err(format!("Conflicting versions for '{}'", id));
+    }
+    let mut entry = ManifestEntry::new(project, Source::Modrinth);
+    entry.version_id = versions.pop();
+    Ok(entry)
+}
+
+/// `project@version` pins a version ID or version_number
+fn split_pin(id: &str) -> (&str, Option<String>) {
+    match id.rsplit_once('@') {
+        Some((project, version)) if !project.is_empty() && !version.is_empty() => {
+            (project, Some(version.to_string()))
+        },
+        _ => (id, None)
+    }
+}
+
+/// Splits a Modrinth project ID, slug, project URL or version URL
+/// (`https://modrinth.com/mod/<slug>/version/<version>`) into the project
+/// and, for version URLs, the pinned version
+pub fn normalize_modrinth_id(input: &str) -> Result<(String, Option<String>), String> {
+    let without_scheme = input.strip_prefix("https://")
+        .or(input.strip_prefix("http://"))
+        .unwrap_or(input);
+    let Some(path) = without_scheme.strip_prefix("modrinth.com/")
+        .or(without_scheme.strip_prefix("www.modrinth.com/"))
+    else {
+        if input.contains('/') || input.contains(':') {
+            return Err(format!("'{}' is not a Modrinth ID, slug or URL", input));
+        }
+        return Ok((input.to_string(), None));
+    };
+    let path = path.split(['?', '#']).next().unwrap_or(path);
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    match segments[..] {
+        [kind, project] if MODRINTH_PROJECT_KINDS.contains(&kind) => {
+            Ok((project.to_string(), None))
+        },
+        [kind, project, "version", version] if MODRINTH_PROJECT_KINDS.contains(&kind) => {
+            Ok((project.to_string(), Some(version.to_string())))
+        },
+        _ => Err(format!("'{}' is not a Modrinth project or version URL", input))
+    }
+}
+
+fn get_source(source: &str) -> Result<Source, String> {
+    match source {
+        "modrinth" => Ok(Source::Modrinth),
+        "curseforge" | "curse" => Ok(Source::Curseforge),
+        _ => Err(format!("Unknown source '{}'", source))
+    }
+}
+
+fn get_version_types(types: &str) -> Result<Vec<String>, String> {
+    types.split(',')
+        .map(|t| match t {
+            "release" | "beta" | "alpha" => Ok(t.to_string()),
+            _ => Err(format!("Unknown version type '{}'", t))
+        })
+        .collect()
+}
+
+fn get_bool(value: &str) -> Result<bool, String> {
+    match value {
+        "true" | "yes" => Ok(true),
+        "false" | "no" => Ok(false),
+        _ => Err(format!("Expected true or false, got '{}'", value))
+    }
+}
+
+fn get_side(side: &str) -> Result<Side, String> {
+    match side {
+        "client" => Ok(Side::Client),
+        "server" => Ok(Side::Server),
+        _ => Err(format!("Unknown side '{}'", side))
+    }
+}
+
+/// Glob match where `*` stands for any run of characters
+fn matches_pattern(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or("");
+    let Some(mut rest) = text.strip_prefix(first) else { return false };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
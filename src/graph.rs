@@ -0,0 +1,153 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Who pulled in what: top-level entries are roots, and each edge points
+/// from a mod to a dependency resolved for it. Nodes are project IDs.
+#[derive(Default)]
+pub struct DependencyGraph {
+    roots: Vec<String>,
+    titles: BTreeMap<String, String>,
+    edges: BTreeMap<String, Vec<String>>,
+}
+
+impl DependencyGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn roots(&self) -> &Vec<String> {
+        &self.roots
+    }
+    pub fn add_root(&mut self, project_id: &str, title: &str) {
+        self.add_node(project_id, title);
+        if !self.roots.iter().any(|r| r == project_id) {
+            self.roots.push(project_id.to_string());
+        }
+    }
+    pub fn add_node(&mut self, project_id: &str, title: &str) {
+        self.titles.insert(project_id.to_string(), title.to_string());
+    }
+    pub fn add_edge(&mut self, parent: &str, child: &str) {
+        let children = self.edges.entry(parent.to_string()).or_default();
+        if !children.iter().any(|c| c == child) {
+            children.push(child.to_string());
+        }
+    }
+    /// Drops the edges out of `project_id`, e.g. once another version of
+    /// it replaces the one they were resolved for
+    pub fn clear_children(&mut self, project_id: &str) {
+        self.edges.remove(project_id);
+    }
+    /// Removes every node no root leads to, returning their IDs
+    pub fn prune_unreachable(&mut self) -> Vec<String> {
+        let mut reachable: BTreeSet<String> = BTreeSet::new();
+        let mut stack: Vec<String> = self.roots.clone();
+        while let Some(node) = stack.pop() {
+            if reachable.insert(node.clone()) {
+                stack.extend(self.children(&node).iter().cloned());
+            }
+        }
+        let pruned: Vec<String> = self.titles.keys()
+            .filter(|id| !reachable.contains(*id))
+            .cloned()
+            .collect();
+        self.titles.retain(|id, _| reachable.contains(id));
+        self.edges.retain(|id, _| reachable.contains(id));
+        pruned
+    }
+    pub fn children(&self, project_id: &str) -> &[String] {
+        self.edges.get(project_id).map_or(&[], Vec::as_slice)
+    }
+    pub fn title(&self, project_id: &str) -> String {
+        self.titles.get(project_id).cloned().unwrap_or(project_id.to_string())
+    }
+    /// Adds every node and edge of `other`, e.g. another provider's part of the set
+    pub fn merge(&mut self, other: DependencyGraph) {
+        self.titles.extend(other.titles);
+        for root in other.roots {
+            if !self.roots.contains(&root) {
+                self.roots.push(root);
+            }
+        }
+        for (parent, children) in other.edges {
+            for child in children {
+                self.add_edge(&parent, &child);
+            }
+        }
+    }
+    /// Project ID for `query`, matched by ID or, ignoring case, by title
+    pub fn find(&self, query: &str) -> Option<&String> {
+        self.titles.get_key_value(query)
+            .map(|(id, _)| id)
+            .or(self.titles.iter()
+                .find(|(_, title)| title.eq_ignore_ascii_case(query))
+                .map(|(id, _)| id)
+            )
+    }
+    /// The graph as an indented tree, one root at a time. A dependency seen
+    /// twice is printed under every mod that needs it.
+    pub fn format_tree(&self) -> String {
+        let mut lines: Vec<String> = Vec::new();
+        for root in &self.roots {
+            self.format_node(root, 0, &mut vec![], &mut lines);
+        }
+        lines.join("\n")
+    }
+    fn format_node(
+        &self,
+        node: &str,
+        depth: usize,
+        path: &mut Vec<String>,
+        lines: &mut Vec<String>
+    ) {
+        let label = format!("{}{} ({})", "  ".repeat(depth), self.title(node), node);
+        if path.iter().any(|p| p == node) {
+            lines.push(format!("{label} [cycle]"));
+            return;
+        }
+        lines.push(label);
+        path.push(node.to_string());
+        for child in self.children(node) {
+            self.format_node(child, depth + 1, path, lines);
+        }
+        path.pop();
+    }
+    /// Every path from a root down to `target`, each listed root first
+    pub fn paths_to(&self, target: &str) -> Vec<Vec<String>> {
+        let mut paths: Vec<Vec<String>> = Vec::new();
+        for root in &self.roots {
+            self.collect_paths(root, target, &mut vec![], &mut paths);
+        }
+        paths
+    }
+    fn collect_paths(
+        &self,
+        node: &str,
+        target: &str,
+        path: &mut Vec<String>,
+        paths: &mut Vec<Vec<String>>
+    ) {
+        if path.iter().any(|p| p == node) {
+            return;
+        }
+        path.push(node.to_string());
+        if node == target {
+            paths.push(path.clone());
+        } else {
+            for child in self.children(node) {
+                self.collect_paths(child, target, path, paths);
+            }
+        }
+        path.pop();
+    }
+    /// `paths_to(target)` with titles, as `A -> B -> C` lines
+    pub fn format_why(&self, target: &str) -> String {
+        self.paths_to(target)
+            .iter()
+            .map(|path| path.iter()
+                .map(|id| self.title(id))
+                .collect::<Vec<String>>()
+                .join(" -> ")
+            )
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
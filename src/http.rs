@@ -0,0 +1,184 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use reqwest::{RequestBuilder, Response, StatusCode};
+
+use crate::provider::ModError;
+
+const REMAINING_HEADER: &str = "x-ratelimit-remaining";
+const RESET_HEADER: &str = "x-ratelimit-reset";
+const MAX_RETRIES: u32 = 3;
+const BASE_DELAY: Duration = Duration::from_millis(500);
+const MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// What the last response said about the current rate limit window
+#[derive(Default)]
+struct Budget {
+    remaining: Option<u32>,
+    reset_at: Option<Instant>,
+}
+
+/// Sends the requests for one API. Waits when the API's rate limit headers
+/// say the window is used up, and retries 429s, 5xx responses and dropped
+/// connections with jittered exponential backoff.
+pub struct Http {
+    client: reqwest::Client,
+    budget: Mutex<Budget>,
+    max_retries: u32,
+    base_delay: Duration,
+}
+
+impl Http {
+    pub fn new(client: &reqwest::Client) -> Self {
+        Http {
+            client: client.clone(),
+            budget: Mutex::new(Budget::default()),
+            max_retries: MAX_RETRIES,
+            base_delay: BASE_DELAY
+        }
+    }
+    /// Retries up to `max_retries` times, waiting about `base_delay` before
+    /// the first and twice as long before each one after
+    pub fn with_retries(mut self, max_retries: u32, base_delay: Duration) -> Self {
+        self.max_retries = max_retries;
+        self.base_delay = base_delay;
+        self
+    }
+    pub fn client(&self) -> &reqwest::Client {
+        &self.client
+    }
+    /// Sends `request`, returning the first 2xx response. Other statuses
+    /// come back as `ModError`s naming `id`, the thing asked for, once
+    /// retries, if any apply, run out.
+    pub async fn send(&self, id: &str, request: RequestBuilder) -> Result<Response, ModError> {
+        let mut attempt: u32 = 0;
+        loop {
+            self.throttle().await;
+            // Only streamed bodies can't be cloned, and those aren't retried
+            let Some(this_try) = request.try_clone() else {
+                let response = request.send().await?;
+                self.record(&response);
+                return check_status(response, id);
+            };
+            let retry_after = match this_try.send().await {
+                Ok(response) => {
+                    self.record(&response);
+                    let status = response.status();
+                    if !is_temporary(status) || attempt >= self.max_retries {
+                        return check_status(response, id);
+                    }
+                    println!("[HTTP/WARNING] {} from {}", status, response.url());
+                    // The reset header describes the rate limit window, which
+                    // only says when to retry if that's what was hit
+                    if status == StatusCode::TOO_MANY_REQUESTS {
+                        reset_delay(&response).map(|d| d.min(MAX_DELAY))
+                    } else {
+                        None
+                    }
+                },
+                Err(e) => {
+                    if !(e.is_timeout() || e.is_connect()) || attempt >= self.max_retries {
+                        return Err(e.into());
+                    }
+                    println!("[HTTP/WARNING] {}", e);
+                    None
+                }
+            };
+            let delay = retry_after.unwrap_or(self.backoff(attempt));
+            println!(
+                "[HTTP] Retrying in {:.1}s (attempt {} of {})",
+                delay.as_secs_f64(),
+                attempt + 1,
+                self.max_retries
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+    /// Waits out the rate limit window when the last response said it was
+    /// spent, otherwise counts this request against it. A spent window
+    /// holds every caller back until it has passed.
+    async fn throttle(&self) {
+        let wait = {
+            let mut budget = self.budget.lock().unwrap_or_else(|e| e.into_inner());
+            match (budget.remaining, budget.reset_at) {
+                (Some(0), Some(reset_at)) if reset_at > Instant::now() => {
+                    reset_at.saturating_duration_since(Instant::now())
+                },
+                (Some(0), _) => {
+                    *budget = Budget::default();
+                    Duration::ZERO
+                },
+                (Some(n), _) => {
+                    budget.remaining = Some(n.saturating_sub(1));
+                    Duration::ZERO
+                },
+                _ => Duration::ZERO
+            }
+        };
+        if !wait.is_zero() {
+            println!("[HTTP] Rate limit reached, waiting {:.1}s", wait.as_secs_f64());
+            tokio::time::sleep(wait).await;
+        }
+    }
+    fn record(&self, response: &Response) {
+        let remaining = header_number(response, REMAINING_HEADER);
+        if remaining.is_none() {
+            return;
+        }
+        let mut budget = self.budget.lock().unwrap_or_else(|e| e.into_inner());
+        budget.remaining = remaining.map(|n| n as u32);
+        budget.reset_at = reset_delay(response).map(|d| Instant::now() + d);
+    }
+    fn backoff(&self, attempt: u32) -> Duration {
+        let full = self.base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(MAX_DELAY);
+        full / 2 + jitter(full / 2)
+    }
+}
+
+/// 429s and 5xx responses are worth another try
+fn is_temporary(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+fn check_status(response: Response, id: &str) -> Result<Response, ModError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let id = id.to_string();
+    let url = response.url().to_string();
+    Err(match status {
+        StatusCode::NOT_FOUND => ModError::NotFound(id, url),
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => ModError::Unauthorized(id, url),
+        StatusCode::TOO_MANY_REQUESTS => ModError::RateLimited(id, url),
+        s if s.is_server_error() => ModError::ServerError(s.as_u16(), id, url),
+        s => ModError::BadStatus(s.as_u16(), id, url)
+    })
+}
+
+fn header_number(response: &Response, name: &str) -> Option<u64> {
+    response.headers()
+        .get(name)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Time until the rate limit window resets, from the seconds in the reset header
+fn reset_delay(response: &Response) -> Option<Duration> {
+    header_number(response, RESET_HEADER).map(Duration::from_secs)
+}
+
+/// Anywhere up to `max`. The clock's sub-second part is random enough to
+/// keep clients that failed together from retrying together.
+fn jitter(max: Duration) -> Duration {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or(0);
+    max.mul_f64(f64::from(nanos % 1000) / 1000.0)
+}
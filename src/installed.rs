@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+use std::{fs, io};
+use std::path::{Path, PathBuf};
+use serde::Serialize;
+
+use crate::modrinth::{self, Modrinth, Version};
+use crate::provider::{FileDigest, ModError, VersionQuery};
+
+/// A `.jar` already sitting in the mods folder, keyed by its sha512
+pub struct InstalledJar {
+    path: PathBuf,
+    sha512: String,
+}
+
+impl InstalledJar {
+    pub fn read(path: PathBuf) -> io::Result<Self> {
+        let sha512 = hex::encode(FileDigest::of_file(&path)?.sha512());
+        Ok(InstalledJar { path, sha512 })
+    }
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+    pub fn filename(&self) -> String {
+        self.path.file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+    pub fn sha512(&self) -> &String {
+        &self.sha512
+    }
+}
+
+/// Every `.jar` directly inside `dir`, sorted by filename
+pub fn list_jars(dir: &Path) -> io::Result<Vec<InstalledJar>> {
+    let mut jars = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() && path.extension().is_some_and(|ext| ext == "jar") {
+            jars.push(InstalledJar::read(path)?);
+        }
+    }
+    jars.sort_by_key(|j| j.filename());
+    Ok(jars)
+}
+
+/// Pairs each jar with the Modrinth version it was published as, if any
+pub async fn identify(
+    modrinth: &Modrinth,
+    jars: Vec<InstalledJar>
+) -> Result<Vec<(InstalledJar, Option<Version>)>, ModError> {
+    if jars.is_empty() {
+        return Ok(Vec::new());
+    }
+    let hashes: Vec<String> = jars.iter().map(|j| j.sha512.clone()).collect();
+    let mut versions = modrinth::get_versions_from_hashes(modrinth, &hashes).await?;
+    Ok(jars.into_iter()
+        .map(|j| {
+            let version = versions.remove(&j.sha512);
+            (j, version)
+        })
+        .collect()
+    )
+}
+
+/// Project titles for the identified jars, keyed by project ID. Projects
+/// that fail to load are left out.
+pub async fn project_titles(
+    modrinth: &Modrinth,
+    jars: &[(InstalledJar, Option<Version>)]
+) -> HashMap<String, String> {
+    let mut ids: Vec<String> = jars.iter()
+        .filter_map(|(_, v)| v.as_ref().map(|v| v.project_id().clone()))
+        .collect();
+    ids.sort();
+    ids.dedup();
+    modrinth::get_projects_from_list(modrinth, &ids)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|p| (p.get_id().clone(), p.get_title().clone()))
+        .collect()
+}
+
+/// Ids file contents for the identified jars: each project once, under a
+/// comment with its title. Unidentified jars are kept as comments.
+pub fn build_manifest(
+    jars: &[(InstalledJar, Option<Version>)],
+    titles: &HashMap<String, String>
+) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    let mut seen: Vec<&String> = Vec::new();
+    for (_, version) in jars {
+        if let Some(v) = version && !seen.contains(&v.project_id()) {
+            seen.push(v.project_id());
+            lines.push(format!("# {}", titles.get(v.project_id()).unwrap_or(v.project_id())));
+            lines.push(v.project_id().clone());
+        }
+    }
+    for (jar, version) in jars {
+        if version.is_none() {
+            lines.push(format!("# {} (not found on Modrinth)", jar.filename()));
+        }
+    }
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+/// An installed jar with a newer version available
+#[derive(Serialize)]
+pub struct OutdatedMod {
+    title: String,
+    project_id: String,
+    filename: String,
+    installed: String,
+    available: String,
+    version_type: String,
+}
+
+impl OutdatedMod {
+    pub fn title(&self) -> &String {
+        &self.title
+    }
+    pub fn project_id(&self) -> &String {
+        &self.project_id
+    }
+    pub fn filename(&self) -> &String {
+        &self.filename
+    }
+    pub fn installed(&self) -> &String {
+        &self.installed
+    }
+    pub fn available(&self) -> &String {
+        &self.available
+    }
+    pub fn version_type(&self) -> &String {
+        &self.version_type
+    }
+    pub fn table_row(&self) -> Vec<String> {
+        vec![
+            self.title.clone(),
+            self.installed.clone(),
+            self.available.clone(),
+            self.version_type.clone()
+        ]
+    }
+}
+
+/// Compares `current` with the newest version for `query`; `None` unless
+/// that one was published later
+pub async fn check_outdated(
+    modrinth: &Modrinth,
+    query: &VersionQuery,
+    jar: &InstalledJar,
+    current: &Version
+) -> Result<Option<OutdatedMod>, ModError> {
+    let top = modrinth::get_top_version(modrinth, current.project_id(), query).await?;
+    // The installed build may be one the query doesn't list, even a newer one
+    if top.date_published() <= current.date_published() {
+        return Ok(None);
+    }
+    let project = modrinth::get_project(modrinth, current.project_id()).await?;
+    Ok(Some(OutdatedMod {
+        title: project.get_title().clone(),
+        project_id: project.get_id().clone(),
+        filename: jar.filename(),
+        installed: current.version_number().clone(),
+        available: top.version_number().clone(),
+        version_type: top.version_type().clone()
+    }))
+}
+
+/// Left-aligned plain text table with a header underline
+pub fn format_table(header: &[&str], rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = header.iter().map(|h| h.len()).collect();
+    for row in rows {
+        for (w, cell) in widths.iter_mut().zip(row) {
+            *w = (*w).max(cell.chars().count());
+        }
+    }
+    let format_row = |cells: Vec<&str>| -> String {
+        cells.iter()
+            .zip(&widths)
+            .map(|(c, w)| format!("{:<w$}", c, w = *w))
+            .collect::<Vec<String>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+    let rule: Vec<String> = widths.iter().map(|w| "-".repeat(*w)).collect();
+    let mut out = vec![
+        format_row(header.to_vec()),
+        format_row(rule.iter().map(String::as_str).collect())
+    ];
+    for row in rows {
+        out.push(format_row(row.iter().map(String::as_str).collect()));
+    }
+    out.join("\n")
+}
@@ -1,14 +1,31 @@
 use std::fs::{self, DirEntry};
 use std::{fmt, io, error};
+use std::io::Write;
 use std::path::{Path, PathBuf};
+use futures::future;
+
+use provider::{ModProvider, VersionInfo, VersionQuery};
 
 #[cfg(test)]
 mod tests;
+pub mod provider;
+pub mod pipeline;
 pub mod modrinth;
+pub mod curseforge;
 pub mod arguments;
 pub mod file_parse;
+pub mod lockfile;
+pub mod settings;
+pub mod mrpack;
+pub mod installed;
+pub mod graph;
+pub mod cache;
+pub mod http;
+pub mod staging;
 
 const DEFAULT_OUT_DIR: &str = "mods";
+const CONFIG_DIR: &str = "config";
+const DEFAULT_PACK_VERSION: &str = "1.0.0";
 const APP_USER_AGENT: &str = concat!(
     "hwschieding/",
     env!("CARGO_PKG_NAME"),
@@ -21,19 +38,34 @@ pub async fn id_from_file<'a>(
     conf: &arguments::Config<'a>,
     client: &reqwest::Client,
     filename: &Path,
-    out_dir: &PathBuf
+    out_dir: &Path
 ) -> Result<(), Box<dyn std::error::Error>>
 {
     println!("Parsing file '{}'...", filename.display());
     let ids = file_parse::parse_ids(filename)?;
 
+    let mut resolution = pipeline::Resolution::default();
     if let Some(modrinth_ids) = ids.modrinth() {
         println!("Handling modrinth ids...");
-        modrinth::handle_list_input(conf, client, modrinth_ids, out_dir).await?;
+        let modrinth = modrinth::Modrinth::from_config(conf, client);
+        resolution.merge(pipeline::resolve_list(conf, &modrinth, modrinth_ids).await);
     };
     if let Some(curse_ids) = ids.curseforge() {
-        for id in curse_ids {
-            println!("Curseforge id '{id}'");
+        println!("Handling curseforge ids...");
+        let cf = curseforge::CurseForge::from_config(conf, client)?;
+        resolution.merge(pipeline::resolve_list(conf, &cf, curse_ids).await);
+    }
+    pipeline::check_resolution(conf, &resolution)?;
+    let mods = resolution.mods();
+    if let Some(report) = conf.report() {
+        return print_report(report, resolution.graph());
+    }
+    if let Some(pack_path) = conf.export_path() {
+        export_mrpack(conf, mods, pack_path, out_dir, conf.mcvs(), conf.loader_as_str())?;
+    } else {
+        pipeline::install_mods(conf, client, mods, out_dir).await?;
+        if !conf.options().get_verify() {
+            pipeline::write_lockfile(conf, &resolution);
         }
     }
     Ok(())
@@ -42,16 +74,377 @@ pub async fn id_from_file<'a>(
 pub async fn single_id<'a>(
     conf: &arguments::Config<'a>,
     client: &reqwest::Client,
-    id: &String,
-    out_dir: &PathBuf
+    id: &str,
+    out_dir: &Path
+) -> Result<(), Box<dyn std::error::Error>>
+{
+    let modrinth = modrinth::Modrinth::from_config(conf, client);
+    let entry = file_parse::entry_from_id(id, *conf.version())
+        .map_err(provider::ModError::BadId)?;
+    let resolution = pipeline::resolve_single(conf, &modrinth, &entry).await?;
+    pipeline::check_resolution(conf, &resolution)?;
+    let mods = resolution.mods();
+    if let Some(report) = conf.report() {
+        return print_report(report, resolution.graph());
+    }
+    if let Some(pack_path) = conf.export_path() {
+        export_mrpack(conf, mods, pack_path, out_dir, conf.mcvs(), conf.loader_as_str())?;
+    } else {
+        pipeline::install_mods(conf, client, mods, out_dir).await?;
+    }
+    Ok(())
+}
+
+pub async fn single_version<'a>(
+    conf: &arguments::Config<'a>,
+    client: &reqwest::Client,
+    version_id: &str,
+    out_dir: &Path
+) -> Result<(), Box<dyn std::error::Error>>
+{
+    let modrinth = modrinth::Modrinth::from_config(conf, client);
+    let resolution = pipeline::resolve_version(conf, &modrinth, version_id).await?;
+    pipeline::check_resolution(conf, &resolution)?;
+    let mods = resolution.mods();
+    if let Some(report) = conf.report() {
+        return print_report(report, resolution.graph());
+    }
+    if let Some(pack_path) = conf.export_path() {
+        export_mrpack(conf, mods, pack_path, out_dir, conf.mcvs(), conf.loader_as_str())?;
+    } else {
+        pipeline::install_mods(conf, client, mods, out_dir).await?;
+    }
+    Ok(())
+}
+
+/// Prints the `tree` or `why` view of a resolved set
+fn print_report(
+    report: &arguments::Report,
+    graph: &graph::DependencyGraph
+) -> Result<(), Box<dyn std::error::Error>>
+{
+    match report {
+        arguments::Report::Tree => println!("{}", graph.format_tree()),
+        arguments::Report::Why(project) => {
+            let Some(id) = graph.find(project) else {
+                return Err(provider::ModError::BadId(
+                    format!("{} is not part of the resolved set", project)
+                ).into());
+            };
+            println!("{}", graph.format_why(id));
+        }
+    }
+    Ok(())
+}
+
+pub async fn from_lockfile<'a>(
+    conf: &arguments::Config<'a>,
+    client: &reqwest::Client,
+    out_dir: &Path
+) -> Result<(), Box<dyn std::error::Error>>
+{
+    println!("Reading lockfile '{}'...", lockfile::LOCKFILE_NAME);
+    let lock = lockfile::Lockfile::read(Path::new(lockfile::LOCKFILE_NAME))?;
+    println!(
+        "[LOCK] Using locked versions for '{}' on '{}'",
+        lock.game_versions(),
+        lock.loader()
+    );
+    let mods = pipeline::mods_from_lockfile(&lock)?;
+    if let Some(pack_path) = conf.export_path() {
+        export_mrpack(conf, &mods, pack_path, out_dir, lock.game_versions(), lock.loader())?;
+    } else {
+        pipeline::install_mods(conf, client, &mods, out_dir).await?;
+    }
+    Ok(())
+}
+
+/// Installs a `.mrpack` into the instance folder given by `-o`, since pack
+/// paths already start at the instance root (`mods/...`, `config/...`)
+pub async fn import_mrpack<'a>(
+    conf: &arguments::Config<'a>,
+    client: &reqwest::Client,
+    pack_path: &Path
+) -> Result<(), Box<dyn std::error::Error>>
+{
+    println!("Reading modpack '{}'...", pack_path.display());
+    let mut pack = mrpack::Modpack::open(pack_path)?;
+    pack.print_info();
+    // The whole instance folder is staged and swapped, so it must be a real one
+    let root = conf.out_dir().ok_or("--mrpack needs -o <instance folder>")?;
+    let staging = staging::Staging::build(root)?;
+    // Overrides configure the pack's mods, so they only go in once all of them did
+    mrpack::install_files(client, pack.index(), conf.side(), staging.path()).await?;
+    let written = pack.extract_overrides(staging.path(), conf.side())?;
+    println!("[MRPACK] Extracted {} override files into '{}'", written, root.display());
+    staging.commit()?;
+    Ok(())
+}
+
+/// Writes `mods` to a `.mrpack` instead of installing them. The pack is
+/// named after the file and pinned to the first of `game_versions`.
+pub fn export_mrpack<'a>(
+    conf: &arguments::Config<'a>,
+    mods: &[pipeline::Mod],
+    pack_path: &Path,
+    out_dir: &Path,
+    game_versions: &str,
+    loader: &str
+) -> Result<(), Box<dyn std::error::Error>>
+{
+    let loader_key = mrpack::loader_dependency(loader)
+        .ok_or(mrpack::PackError::BadEntry(format!("No modpack loader for '{}'", loader)))?;
+    let loader_version = conf.loader_version().unwrap_or_default();
+    let mut files: Vec<mrpack::PackFile> = Vec::new();
+    let mut failed: usize = 0;
+    for m in mods {
+        match mrpack::PackFile::from_mod(m, out_dir) {
+            Ok(f) => files.push(f),
+            Err(e) => {
+                println!("{e}");
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(mrpack::PackError::BadEntry(
+            format!("{} out of {} mods could not be added; not writing '{}'", failed, mods.len(), pack_path.display())
+        ).into());
+    }
+    let name = pack_path.file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or(String::from("mcmodgetter"));
+    let game_version = game_versions.split(',').next().unwrap_or(game_versions);
+    let index = mrpack::PackIndex::build(
+        name,
+        DEFAULT_PACK_VERSION.to_string(),
+        game_version,
+        (loader_key, loader_version),
+        files
+    );
+    let config_dir = Path::new(CONFIG_DIR);
+    let overrides = if conf.options().get_bundle_config() {
+        if config_dir.is_dir() {
+            Some(config_dir)
+        } else {
+            println!("[MRPACK/WARNING] No '{}' folder to bundle", CONFIG_DIR);
+            None
+        }
+    } else {
+        None
+    };
+    let bundled = index.write(pack_path, overrides)?;
+    println!(
+        "[MRPACK] Wrote {} mods and {} config files to '{}'",
+        index.files().len(),
+        bundled,
+        pack_path.display()
+    );
+    Ok(())
+}
+
+/// Replaces every installed Modrinth jar in `out_dir` with the newest
+/// version matching the config, then prints what changed
+pub async fn update_mods<'a>(
+    conf: &arguments::Config<'a>,
+    client: &reqwest::Client,
+    out_dir: &Path
+) -> Result<(), Box<dyn std::error::Error>>
+{
+    let modrinth = modrinth::Modrinth::from_config(conf, client);
+    let query = VersionQuery::build_query(conf.mcvs(), &conf.loader_as_string());
+    fs::create_dir_all(out_dir)?;
+    println!("Identifying mods in '{}'...", out_dir.display());
+    let jars = installed::identify(&modrinth, installed::list_jars(out_dir)?).await?;
+    let mut staging = staging::Staging::build(out_dir)?;
+    let mut rows: Vec<Vec<String>> = Vec::new();
+    let mut failed: usize = 0;
+    let mut total: usize = 0;
+    for (jar, version) in jars {
+        let Some(version) = version else {
+            println!("[UPDATE] '{}' is not a known Modrinth file. Skipping...", jar.filename());
+            continue;
+        };
+        total += 1;
+        match update_jar(conf, client, &modrinth, &query, &jar, version.into(), &mut staging).await {
+            Ok(row) => rows.push(row),
+            Err(e) => {
+                println!("{e}");
+                failed += 1;
+            }
+        }
+    }
+    // Dropping the staging folder leaves every jar as it was
+    if failed > 0 {
+        return Err(Box::new(UpdateError::Incomplete(failed, total)));
+    }
+    staging.commit()?;
+    println!("\n{}", installed::format_table(&["Mod", "Before", "After"], &rows));
+    Ok(())
+}
+
+async fn update_jar<'a>(
+    conf: &arguments::Config<'a>,
+    client: &reqwest::Client,
+    modrinth: &modrinth::Modrinth,
+    query: &VersionQuery,
+    jar: &installed::InstalledJar,
+    current: VersionInfo,
+    staging: &mut staging::Staging
+) -> Result<Vec<String>, Box<dyn std::error::Error>>
+{
+    let top: VersionInfo = modrinth::get_top_version(modrinth, current.project_id(), query)
+        .await?
+        .into();
+    // Same rule as `outdated`: never swap in an older build
+    if top.published() <= current.published() {
+        let project = modrinth.resolve_project(current.project_id()).await?;
+        println!("[UPDATE] {} is up to date", project.get_title());
+        return Ok(vec![
+            project.get_title().clone(),
+            current.version_number().clone(),
+            String::from("(up to date)")
+        ]);
+    }
+    let after = top.version_number().clone();
+    let new_mod = pipeline::Mod::build_from_version(modrinth, top).await?;
+    let staged_dir = staging.path().clone();
+    if let Err(e) = new_mod.download_with_retries(client, &staged_dir, conf.options().get_retries()).await {
+        println!("{e}");
+        return Err(Box::new(pipeline::DownloadError::BadHash(
+            format!("Could not update '{}'; keeping {}", new_mod.title(), jar.filename())
+        )));
+    }
+    if *new_mod.filename() != jar.filename() {
+        retire_jar(&staged_dir.join(jar.filename()), conf.archive_dir())?;
+    }
+    staging.replace_project(new_mod.project_id(), new_mod.filename())?;
+    Ok(vec![new_mod.title().clone(), current.version_number().clone(), after])
+}
+
+/// Lists installed Modrinth jars that have a newer matching version,
+/// as a table or, with `--json`, a JSON array and nothing else
+pub async fn outdated_mods<'a>(
+    conf: &arguments::Config<'a>,
+    client: &reqwest::Client,
+    out_dir: &Path
+) -> Result<(), Box<dyn std::error::Error>>
+{
+    let json = conf.options().get_json();
+    let modrinth = modrinth::Modrinth::from_config(conf, client);
+    let query = VersionQuery::build_query(conf.mcvs(), &conf.loader_as_string());
+    if !json {
+        println!("Identifying mods in '{}'...", out_dir.display());
+    }
+    let jars = installed::identify(&modrinth, installed::list_jars(out_dir)?).await?;
+    let checks = future::join_all(jars.iter().filter_map(|(jar, version)| {
+        if version.is_none() && !json {
+            println!("[OUTDATED] '{}' is not a known Modrinth file. Skipping...", jar.filename());
+        }
+        version.as_ref().map(|v| installed::check_outdated(&modrinth, &query, jar, v))
+    })).await;
+    let mut outdated: Vec<installed::OutdatedMod> = Vec::new();
+    for check in checks {
+        match check {
+            Ok(Some(o)) => outdated.push(o),
+            Ok(None) => (),
+            Err(e) => eprintln!("{e}")
+        }
+    }
+    if json {
+        println!("{}", serde_json::to_string_pretty(&outdated)?);
+    } else if outdated.is_empty() {
+        println!("All identified mods are up to date");
+    } else {
+        let rows: Vec<Vec<String>> = outdated.iter().map(installed::OutdatedMod::table_row).collect();
+        println!("\n{}", installed::format_table(&["Mod", "Installed", "Available", "Type"], &rows));
+    }
+    Ok(())
+}
+
+/// Reports the Modrinth project and version behind every jar in `out_dir`,
+/// flagging jars Modrinth doesn't know as unmanaged
+pub async fn scan_mods<'a>(
+    conf: &arguments::Config<'a>,
+    client: &reqwest::Client,
+    out_dir: &Path
+) -> Result<(), Box<dyn std::error::Error>>
+{
+    let modrinth = modrinth::Modrinth::from_config(conf, client);
+    println!("Scanning '{}'...", out_dir.display());
+    let jars = installed::identify(&modrinth, installed::list_jars(out_dir)?).await?;
+    let titles = installed::project_titles(&modrinth, &jars).await;
+    let mut unmanaged: u32 = 0;
+    let rows: Vec<Vec<String>> = jars.iter()
+        .map(|(jar, version)| match version {
+            Some(v) => vec![
+                jar.filename(),
+                titles.get(v.project_id()).unwrap_or(v.project_id()).clone(),
+                v.version_number().clone()
+            ],
+            None => {
+                unmanaged += 1;
+                vec![jar.filename(), String::from("UNMANAGED"), String::new()]
+            }
+        })
+        .collect();
+    println!("\n{}", installed::format_table(&["File", "Project", "Version"], &rows));
+    println!("\n{} jars, {} unmanaged", jars.len(), unmanaged);
+    Ok(())
+}
+
+/// Writes an ids file for the jars in `out_dir` so a hand-built folder
+/// can be managed with --readfile. Never overwrites an existing file.
+pub async fn import_dir<'a>(
+    conf: &arguments::Config<'a>,
+    client: &reqwest::Client,
+    manifest: &Path,
+    out_dir: &Path
 ) -> Result<(), Box<dyn std::error::Error>>
 {
-    modrinth::handle_single_input(conf, client, id, out_dir).await?;
+    let modrinth = modrinth::Modrinth::from_config(conf, client);
+    fs::create_dir_all(out_dir)?;
+    println!("Identifying mods in '{}'...", out_dir.display());
+    let jars = installed::identify(&modrinth, installed::list_jars(out_dir)?).await?;
+    let titles = installed::project_titles(&modrinth, &jars).await;
+    let mut f_out = fs::OpenOptions::new().write(true).create_new(true).open(manifest)?;
+    f_out.write_all(installed::build_manifest(&jars, &titles).as_bytes())?;
+    let unknown = jars.iter().filter(|(_, v)| v.is_none()).count();
+    println!(
+        "Wrote '{}' from {} jars ({} not identified)",
+        manifest.display(),
+        jars.len(),
+        unknown
+    );
+    Ok(())
+}
+
+/// Moves a superseded jar into `archive_dir`, or deletes it without one
+fn retire_jar(path: &Path, archive_dir: &Option<&Path>) -> io::Result<()> {
+    match archive_dir {
+        Some(dir) => {
+            fs::create_dir_all(dir)?;
+            let target = dir.join(path.file_name().unwrap_or_default());
+            match fs::rename(path, &target) {
+                // The archive can be on another drive, where a rename can't reach
+                Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+                    fs::copy(path, &target)?;
+                    fs::remove_file(path)?;
+                }
+                result => result?
+            }
+            println!("[UPDATE] Archived {} to {}", path.display(), target.display());
+        }
+        None => {
+            fs::remove_file(path)?;
+            println!("[REMOVAL] Removed entry {}", path.display());
+        }
+    }
     Ok(())
 }
 
 pub fn clear_mods(
-    out_dir: &PathBuf
+    out_dir: &Path
 ) -> Result<(), Box<dyn std::error::Error>>
 {
     println!("Delete all '.jar' files in directory {}? (y/n)",
@@ -60,41 +453,98 @@ pub fn clear_mods(
     let mut user_ans = String::new();
     io::stdin().read_line(&mut user_ans)?;
     if user_ans.trim().to_lowercase() == "y" {
-        clear_dir(&out_dir)?;
+        clear_dir(out_dir)?;
     }
     Ok(())
 }
 
+/// Puts back the mod folder the last install replaced
+pub fn rollback_mods(
+    out_dir: &Path
+) -> Result<(), Box<dyn std::error::Error>>
+{
+    staging::rollback(out_dir)?;
+    Ok(())
+}
+
 pub fn create_client() -> Result<reqwest::Client, reqwest::Error> {
     reqwest::Client::builder()
         .user_agent(APP_USER_AGENT)
         .build()
 }
 
-pub fn get_out_dir(conf_dir: &Option<&Path>) -> Result<PathBuf, io::Error> {
-    let path = conf_dir.unwrap_or(Path::new(DEFAULT_OUT_DIR));
-    fs::create_dir_all(path)?;
-    Ok(PathBuf::from(path))
+/// The mod folder from `-o`, or the default. It isn't created here; only
+/// the installs that write jars into it do that.
+pub fn get_out_dir(conf_dir: &Option<&Path>) -> PathBuf {
+    PathBuf::from(conf_dir.unwrap_or(Path::new(DEFAULT_OUT_DIR)))
 }
 
-pub fn help() -> () {
+pub fn help() {
     println!(
         "COMMANDS:
   checkmods: Verifies mods in mod folder against specified options
+  scanmods: Identifies every jar in mod folder by hash and flags unmanaged ones
+  import-dir <file>: Writes an ids file for --readfile from the jars in mod folder
+  update: Replaces installed Modrinth mods with their newest matching version
+  outdated: Lists installed Modrinth mods with a newer matching version (add --json for JSON)
   clearmods: Removes all .jar files in specified mod folder (use -o)
+  rollback: Restores the mod folder from before the last install (kept as <folder>.previous)
+  tree: Prints the resolved dependency tree instead of downloading
+  why <project>: Prints every path that pulls in <project> (ID or title)
+    *tree and why work with -id, -version and --readfile
 
   OPTIONS:
   -id <string>: Specifies single modrinth ID to download
+    *IDs here and in --readfile files may also be slugs (sodium), project URLs
+     or version URLs (https://modrinth.com/mod/sodium/version/<version>)
+    *<id>@<version> pins a version ID or version_number, e.g. sodium@mc1.21.8-0.7.2-fabric
+  -version <string>: Pins the -id project to a version ID or version_number,
+    or on its own installs that Modrinth version ID
   --readfile <filename>: Specifies filename of modrinth IDs to download
+    *One entry per line: <id>[@<version>] [-curse|-modrinth] [key=value ...]
+     keys: version=<version id>, version_number=<pattern, * matches anything>,
+     types=<release,beta,alpha>, loader=<loader>, side=<client|server>,
+     optional=<true|false>, source=<modrinth|curseforge>
+    *Blank lines are skipped and '#' starts a comment, on its own line or after an entry
+  --locked: Installs the exact versions pinned in mcmodgetter.lock; with --readfile,
+    installs those instead of resolving the file again. Can't be used with any other mode
+  --mrpack <file>: Installs a Modrinth modpack (.mrpack), including its overrides;
+    needs -o, the instance folder the pack's mods/ and overrides go into
   *One of the above is required for a search
+  *Installing with --readfile writes mcmodgetter.lock in the local directory once
+   every download succeeded, unless some entries could not be resolved
+
+  --export <file>: Writes the resolved mods to a Modrinth modpack (.mrpack)
+    instead of downloading them; needs --loader-version
+  --loader-version <version>: Loader version recorded in an exported modpack
+  --with-config: Bundles the local config folder into an exported modpack
 
   -mcv <minecraft version> [REQUIRED]: Specifies MC version to query for mods
   -l <mod loader> [DEFAULT=fabric]: Specifies mod loader to query for (fabric, forge, etc)
   *To query for multiple versions/loaders, separate by commas(,) with no spaces
 
   -o <folder> [DEFAULT=mods]: Specifies output folder for mods relative to local directory
+    *With --mrpack this is the instance folder instead [DEFAULT=.]
+  --side <client|server> [DEFAULT=client]: Which side's files to install from a modpack
+    or ids file
+
+  --cursekey <key>: CurseForge API key for '-curse' entries (or set CURSEFORGE_API_KEY)
+  --modrinth-url <url>: Modrinth API base URL (or set MCMODGETTER_MODRINTH_URL)
+  --config <file> [DEFAULT=mcmodgetter.toml]: Reads modrinth_url, curseforge_url
+    and curseforge_api_key from a TOML file; flags and env vars take priority
+
+  --archive <folder>: With update, moves replaced jars here instead of deleting them
 
   --skipdeps: Skip searching for and downloading mod dependencies
+  --stats: Prints how many API requests resolving the mods took
+  --prefer-newest: When mods pin different versions of one dependency, installs
+    the newest of them instead of stopping
+  --retries <n> [DEFAULT=2]: Tries a failed or mismatched download this many more
+    times before counting it as failed
+  --allow-incompatible: Warns instead of failing when selected mods declare each
+    other incompatible
+  --optional <all|id,...>: Also installs optional dependencies, all of them or
+    only these project IDs; optional=true on an ids file line does it for one mod
   
   -h, --help, -help: Show this help prompt"
     )
@@ -130,6 +580,21 @@ impl From<io::Error> for RemovalError {
     }    
 }
 
+#[derive(Debug)]
+pub enum UpdateError {
+    Incomplete(usize, usize)
+}
+
+impl fmt::Display for UpdateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Incomplete(failed, total) => write!(f, "[UPDATE/ERROR] {failed} out of {total} mods failed to update; no mods were changed")
+        }
+    }
+}
+
+impl error::Error for UpdateError {}
+
 fn remove_jar(entry: &DirEntry) -> Result<(), RemovalError> {
     let path = entry.path();
     if let Some(ext) = path.extension() && ext == "jar"{
@@ -141,10 +606,9 @@ fn remove_jar(entry: &DirEntry) -> Result<(), RemovalError> {
     }
 }
 
-fn clear_dir(out_dir: &PathBuf) -> io::Result<()>{
+fn clear_dir(out_dir: &Path) -> io::Result<()>{
     println!("[REMOVAL] Clearing folder {}...", out_dir.display());
     let entries = fs::read_dir(out_dir)?
-    .into_iter()
     .filter_map(|ent_res| {
         match ent_res {
             Ok(de) => Some(de),
@@ -0,0 +1,140 @@
+use std::fs;
+use std::path::Path;
+use std::{fmt, io, error};
+use serde::{Serialize, Deserialize};
+
+pub const LOCKFILE_NAME: &str = "mcmodgetter.lock";
+
+#[derive(Debug)]
+pub enum LockError {
+    BadFile(io::Error),
+    BadFormat(serde_json::Error),
+}
+
+impl fmt::Display for LockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BadFile(err) => write!(f, "[LOCK/ERROR] Bad lockfile: {}", err),
+            Self::BadFormat(err) => write!(f, "[LOCK/ERROR] Could not parse lockfile: {}", err),
+        }
+    }
+}
+
+impl error::Error for LockError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::BadFile(err) => Some(err),
+            Self::BadFormat(err) => Some(err),
+        }
+    }
+}
+
+impl From<io::Error> for LockError {
+    fn from(value: io::Error) -> Self {
+        Self::BadFile(value)
+    }
+}
+
+impl From<serde_json::Error> for LockError {
+    fn from(value: serde_json::Error) -> Self {
+        Self::BadFormat(value)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct LockedMod {
+    title: String,
+    project_id: String,
+    version_id: String,
+    version_name: String,
+    filename: String,
+    url: String,
+    #[serde(flatten)]
+    hashes: LockedHashes,
+}
+
+impl LockedMod {
+    pub fn new(
+        title: String,
+        project_id: String,
+        version_id: String,
+        version_name: String,
+        filename: String,
+        url: String,
+        hashes: LockedHashes
+    ) -> Self {
+        LockedMod { title, project_id, version_id, version_name, filename, url, hashes }
+    }
+    pub fn title(&self) -> &String {
+        &self.title
+    }
+    pub fn project_id(&self) -> &String {
+        &self.project_id
+    }
+    pub fn version_id(&self) -> &String {
+        &self.version_id
+    }
+    pub fn version_name(&self) -> &String {
+        &self.version_name
+    }
+    pub fn filename(&self) -> &String {
+        &self.filename
+    }
+    pub fn url(&self) -> &String {
+        &self.url
+    }
+    pub fn sha512(&self) -> &Option<String> {
+        &self.hashes.sha512
+    }
+    pub fn sha1(&self) -> &Option<String> {
+        &self.hashes.sha1
+    }
+}
+
+/// Hex-encoded hashes; Modrinth files carry sha512, CurseForge only sha1
+#[derive(Serialize, Deserialize)]
+pub struct LockedHashes {
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    sha512: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    sha1: Option<String>,
+}
+
+impl LockedHashes {
+    pub fn new(sha512: Option<String>, sha1: Option<String>) -> Self {
+        LockedHashes { sha512, sha1 }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Lockfile {
+    game_versions: String,
+    loader: String,
+    mods: Vec<LockedMod>,
+}
+
+impl Lockfile {
+    pub fn build(game_versions: String, loader: String, mut mods: Vec<LockedMod>) -> Self {
+        // Sorted so the file diffs cleanly between runs
+        mods.sort_by(|a, b| a.project_id.cmp(&b.project_id));
+        Lockfile { game_versions, loader, mods }
+    }
+    pub fn read(path: &Path) -> Result<Self, LockError> {
+        let contents = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+    pub fn write(&self, path: &Path) -> Result<(), LockError> {
+        let contents = serde_json::to_string_pretty(self)?;
+        fs::write(path, contents)?;
+        Ok(())
+    }
+    pub fn game_versions(&self) -> &String {
+        &self.game_versions
+    }
+    pub fn loader(&self) -> &String {
+        &self.loader
+    }
+    pub fn mods(&self) -> &Vec<LockedMod> {
+        &self.mods
+    }
+}
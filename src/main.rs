@@ -4,10 +4,18 @@ use std::error::Error;
 use mcmodgetter::{
     clear_mods,
     create_client,
+    from_lockfile,
     get_out_dir,
     help,
     id_from_file,
-    single_id
+    import_dir,
+    outdated_mods,
+    scan_mods,
+    import_mrpack,
+    rollback_mods,
+    single_id,
+    single_version,
+    update_mods
 };
 use mcmodgetter::arguments::{Config, AppMode};
 
@@ -27,16 +35,17 @@ async fn main() {
     }
 }
 
-async fn run<'a>(conf: Config<'a>) -> Result<(), Box<dyn Error>> {
+async fn run<'a>(mut conf: Config<'a>) -> Result<(), Box<dyn Error>> {
     // println!("Starting...");
+    conf.load_settings()?;
     let client = create_client()?;
-    let out_dir = get_out_dir(&conf.out_dir())?;
+    let out_dir = get_out_dir(conf.out_dir());
     match conf.mode() {
         AppMode::IdFromFile(filename) => {
             id_from_file(
                 &conf,
                 &client,
-                &filename, 
+                filename, 
                 &out_dir
             ).await?;
         },
@@ -44,13 +53,67 @@ async fn run<'a>(conf: Config<'a>) -> Result<(), Box<dyn Error>> {
             single_id(
                 &conf,
                 &client,
-                &id,
+                id,
+                &out_dir
+            ).await?;
+        },
+        AppMode::ImportPack(pack_path) => {
+            import_mrpack(
+                &conf,
+                &client,
+                pack_path
+            ).await?;
+        },
+        AppMode::Update => {
+            update_mods(
+                &conf,
+                &client,
+                &out_dir
+            ).await?;
+        },
+        AppMode::Outdated => {
+            outdated_mods(
+                &conf,
+                &client,
+                &out_dir
+            ).await?;
+        },
+        AppMode::Scan => {
+            scan_mods(
+                &conf,
+                &client,
+                &out_dir
+            ).await?;
+        },
+        AppMode::ImportDir(manifest) => {
+            import_dir(
+                &conf,
+                &client,
+                manifest,
+                &out_dir
+            ).await?;
+        },
+        AppMode::SingleVersion(version_id) => {
+            single_version(
+                &conf,
+                &client,
+                version_id,
+                &out_dir
+            ).await?;
+        },
+        AppMode::Locked => {
+            from_lockfile(
+                &conf,
+                &client,
                 &out_dir
             ).await?;
         },
         AppMode::ClearMods => {
             clear_mods(&out_dir)?;
         },
+        AppMode::Rollback => {
+            rollback_mods(&out_dir)?;
+        },
         AppMode::Help => {
             help();
         }
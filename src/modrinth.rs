@@ -1,351 +1,123 @@
-use std::pin::Pin;
-use std::{fmt, fs, error};
-use std::collections::HashSet;
-use std::io::{self, Write};
-use std::path::{self, PathBuf};
+use std::collections::HashMap;
+use std::time::Duration;
 use futures::future;
-use serde::{Serialize, Deserialize, Deserializer};
-use serde::de::{Error};
-use sha2::digest::generic_array::{ArrayLength, GenericArray};
-use sha2::{Sha512, Digest};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde::de::{DeserializeOwned, Error};
 
 use crate::arguments;
-
-static MODRINTH_URL: &str = "https://api.modrinth.com";
-
-#[derive(Debug)]
-pub enum ModError {
-    NoFileForProj(String),
-    BadRequest(reqwest::Error),
-    NoVersionForId(String),
-    NoDependency(String),
-}
-
-impl fmt::Display for ModError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Self::NoFileForProj(proj_title) => write!(f, "[MODRINTH/ERROR] No file for project: {}", proj_title),
-            Self::BadRequest(err) => write!(f, "[MODRINTH/ERROR] Bad request: {}", err),
-            Self::NoVersionForId(id) => write!(f, "[MODRINTH/ERROR] No version for ID: {}", id),
-            Self::NoDependency(msg) => write!(f, "[MODRINTH/ERROR] No dependency: {}", msg)
+use crate::http::Http;
+use crate::provider::{
+    DependencyInfo,
+    DependencyKind,
+    FileHashes,
+    FileInfo,
+    ModError,
+    ModProvider,
+    ProjectInfo,
+    VersionInfo,
+    VersionQuery
+};
+
+pub static MODRINTH_URL: &str = "https://api.modrinth.com";
+const HASH_ALGORITHM: &str = "sha512";
+/// Most IDs per `/v2/projects` or `/v2/versions` request, which keeps the
+/// encoded query around 1.5 KB
+const BULK_CHUNK_SIZE: usize = 100;
+
+/// API context: every request goes through `client` to `base_url`, so a
+/// Labrinth-compatible mirror or a local stand-in can replace the live API.
+pub struct Modrinth {
+    http: Http,
+    base_url: String,
+}
+
+impl Modrinth {
+    pub fn build(client: &reqwest::Client, base_url: &str) -> Self {
+        Modrinth {
+            http: Http::new(client),
+            base_url: base_url.trim_end_matches('/').to_string()
         }
     }
-}
-
-impl error::Error for ModError {
-    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
-        match self {
-            Self::BadRequest(err) => Some(err),
-            _ => None
-        }
+    pub fn from_config(conf: &arguments::Config<'_>, client: &reqwest::Client) -> Self {
+        let base_url = conf.modrinth_url().unwrap_or(MODRINTH_URL.to_string());
+        Self::build(client, &base_url)
     }
-}
-
-impl From<reqwest::Error> for ModError {
-    fn from(value: reqwest::Error) -> Self {
-        Self::BadRequest(value)
+    pub fn base_url(&self) -> &String {
+        &self.base_url
     }
-}
-
-#[derive(Debug)]
-pub enum DownloadError {
-    BadRequest(reqwest::Error),
-    BadFile(io::Error),
-    BadHash(String),
-}
-
-impl fmt::Display for DownloadError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Self::BadRequest(err) => write!(f, "[MODRINTH/DOWNLOAD/ERROR] Bad request: {}", err),
-            Self::BadFile(err) => write!(f, "[MODRINTH/DOWNLOAD/ERROR] Bad file: {}", err),
-            Self::BadHash(msg) => write!(f, "[MODRINTH/DOWNLOAD/ERROR] Bad hash: {}", msg),
-        }
+    pub fn with_retries(mut self, max_retries: u32, base_delay: Duration) -> Self {
+        self.http = self.http.with_retries(max_retries, base_delay);
+        self
     }
 }
 
-impl error::Error for DownloadError {
-    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
-        match self {
-            Self::BadRequest(err) => Some(err),
-            Self::BadFile(err) => Some(err),
-            _ => None
-        }
-    }
-}
-
-impl From<reqwest::Error> for DownloadError {
-    fn from(value: reqwest::Error) -> Self {
-        Self::BadRequest(value)
-    }
-}
-
-impl From<std::io::Error> for DownloadError {
-    fn from(value: std::io::Error) -> Self {
-        Self::BadFile(value)
-    }
-}
-
-pub enum VerificationResult {
-    Ok(String),
-    Err(String)
-}
-
-impl VerificationResult {
-    pub fn print(&self) -> () {
-        match self {
-            Self::Ok(v) => {
-                println!("[MODRINTH/VERIFY] {v}")
-            }
-            Self::Err(e) => {
-                println!("[MODRINTH/VERIFY/ERROR] {e}")
-            }
-        }
-    }
-    pub fn is_ok(&self) -> bool {
-        if let Self::Ok(_) = self {
-            true
-        } else {
-            false
-        }
-    }
-}
-
-pub struct Mod {
-    title: String,
-    project_id: String,
-    version_name: String,
-    _version_id: String,
-    file: ModrinthFile,
-    dependencies: Vec<RequiredDependency>,
-}
-
-impl Mod {
-    pub fn title(&self) -> &String {
-        &self.title
-    }
-    pub fn version_name(&self) -> &String {
-        &self.version_name
+impl ModProvider for Modrinth {
+    fn tag(&self) -> &'static str {
+        "MODRINTH"
     }
-    pub fn filename(&self) -> &String {
-        self.file.filename()
-    }
-    pub fn dependencies(&self) -> &Vec<RequiredDependency> {
-        &self.dependencies
+    fn bulk_requests(&self, count: usize) -> usize {
+        count.div_ceil(BULK_CHUNK_SIZE)
     }
-    fn build(
-        proj: Project,
-        ver: Version,
-        primary_file_idx: usize,
-    ) -> Self {
-        println!("[MODRINTH] Found mod '{}' for id '{}'", proj.get_title(), proj.get_id());
-        Mod { 
-            title: proj.get_title().clone(),
-            project_id: proj.get_id().clone(),
-            version_name: ver.name().clone(),
-            _version_id: ver.id().clone(),
-            file: ver.files()[primary_file_idx].clone(),
-            dependencies: ver.dependencies().clone()
-        }
+    async fn resolve_project(&self, id: &str) -> Result<ProjectInfo, ModError> {
+        Ok(get_project(self, id).await?.into())
     }
-    pub async fn build_from_project_id(
-        client: &reqwest::Client,
-        project_id: String,
+    async fn list_versions(
+        &self,
+        project_id: &str,
         query: &VersionQuery
-    ) -> Result<Self, ModError> {
-        println!("[MODRINTH] Searching for project id '{}'", project_id);
-        let proj = get_project(client, &project_id).await?;
-        let top_version = get_top_version(client, &project_id, query).await?;
-        let primary_file_idx = search_for_primary_file(top_version.files())
-        .ok_or(ModError::NoFileForProj(
-            format!("Couldn't find file for project {}", proj.get_title())
-        ))?;
-        Ok(Self::build(proj, top_version, primary_file_idx))
-    }
-    pub async fn build_from_version_id(
-        client: & reqwest::Client,
-        version_id: String,
-    ) -> Result<Self, ModError> {
-        println!("[MODRINTH] Searching for version id '{}'", version_id);
-        let ver = get_version_from_version_id(client, &version_id).await?;
-        let proj = get_project(client, &ver.project_id()).await?;
-        let primary_file_idx = search_for_primary_file(ver.files())
-        .ok_or(ModError::NoFileForProj(
-            proj.get_title().to_string()
-        ))?;
-        Ok(Self::build(proj, ver, primary_file_idx))
-    }
-    pub async fn build_from_version(
-        client: &reqwest::Client,
-        ver: Version
-    ) -> Result<Self, ModError> {
-        println!("[MODRINTH] Using version id '{}'", ver.id());
-        let proj = get_project(client, ver.project_id()).await?;
-        let primary_file_idx = search_for_primary_file(ver.files())
-        .ok_or(ModError::NoFileForProj(
-            proj.get_title().to_string()
-        ))?;
-        Ok(Self::build(proj, ver, primary_file_idx))
-    }
-    pub fn verify_against(&self, file_path: &PathBuf) -> FileVerification {
-        if !path::Path::exists(&file_path) {
-            return FileVerification::NotExists
-        }
-        match fs::read(&file_path) {
-            Ok(bytes) => {
-                if self.file.hashes.check512(&Sha512::digest(bytes)) {
-                    FileVerification::Ok
-                } else {
-                    FileVerification::BadHash
-                }
-            }
-            Err(_) => FileVerification::BadFile
-        }
-    }
-    async fn check_dep_against(
-        dep_ver: &Version,
-        check_against: &Option<HashSet<&String>>,
-    ) -> bool {
-        if let Some(check) = check_against 
-        && check.contains(dep_ver.project_id()) { false }
-        else { true }
+    ) -> Result<Vec<VersionInfo>, ModError> {
+        Ok(get_version(self, project_id, query)
+            .await?
+            .into_iter()
+            .map(VersionInfo::from)
+            .collect()
+        )
     }
-    pub async fn get_dependencies(
+    async fn get_version(
         &self,
-        client: &reqwest::Client,
-        query: &VersionQuery,
-        check_against: Option<&Vec<Mod>>
-    ) -> Vec<Self> {
-        let mut out: Vec<Self> = Vec::new();
-        let mut check_set: Option<HashSet<&String>> = None;
-        if let Some(c) = check_against {
-            check_set = Some(
-                c.iter()
-                .map(|x| {
-                    &x.project_id
-                })
-                .collect()
-            );
-        }
-        for dep in self.dependencies() {
-            let dep_ver = dep.resolve_to_version(client, query).await;
-            if let Ok(ver) = dep_ver
-            && Self::check_dep_against(&ver, &check_set).await
-            && let Ok(m) = Mod::build_from_version(client, ver).await {
-                out.push(m);
-            };
-        }
-        out
+        project_id: Option<&str>,
+        version_id: &str
+    ) -> Result<VersionInfo, ModError> {
+        // Scoped to a project, `version_id` may also be a version_number
+        let version = match project_id {
+            Some(p) => get_project_version(self, p, version_id).await?,
+            None => get_version_from_version_id(self, version_id).await?
+        };
+        Ok(version.into())
     }
-    pub async fn download(
-        &self,
-        client: &reqwest::Client,
-        out_dir: &PathBuf
-    ) -> Result<(), DownloadError> {
-        let file_path = out_dir.join(self.filename());
-        match self.verify_against(&file_path){
-            FileVerification::Ok => {
-                println!("[MODRINTH/DOWNLOAD] {} already present. Skipping download...", self.title());
-                return Ok(());
-            }
-            FileVerification::BadHash => {
-                println!("[MODRINTH/DOWNLOAD/WARNING] File present for {}, but hashes do not match. Continuing with download...", self.title());
-            }
-            FileVerification::BadFile => {
-                println!("[MODRINTH/DOWNLOAD/WARNING] File present for {}, but something is wrong. Continuing with download...", self.title());
-            }
-            FileVerification::NotExists => {
-                println!("[MODRINTH/DOWNLOAD] Downloading file {} for {}", self.file.filename(), self.title());
-            }
-        }
-        let res = client.get(self.file.url())
-            .send()
+    async fn resolve_projects(&self, ids: &[String]) -> Result<Vec<ProjectInfo>, ModError> {
+        Ok(get_projects_from_list(self, ids)
             .await?
-            .bytes()
-            .await?;
-        if self.file.hashes.check512(&Sha512::digest(&res)) {
-            println!("[MODRINTH/DOWNLOAD] Hashes match. Writing to file...");
-            let mut f_out = fs::File::create(
-                file_path
-            )?;
-            f_out.write_all(&res)?;
-            println!("[MODRINTH/DOWNLOAD] Successfully downloaded {}", self.file.filename());
-        } else {
-            DownloadError::BadHash(
-                format!("Hashes do not match for file '{}'. Skipping download...",
-                    self.file.filename()
-                )
-            );
-        }
-        Ok(())
+            .into_iter()
+            .map(ProjectInfo::from)
+            .collect()
+        )
     }
-    fn verify(
-        &self,
-        out_dir: &PathBuf
-    ) -> VerificationResult
-    {
-        let file_path = out_dir.join(self.filename());
-        match self.verify_against(&file_path) {
-            FileVerification::Ok => VerificationResult::Ok(
-                format!("Successfully verified '{}'", self.filename())
-            ),
-            FileVerification::NotExists => VerificationResult::Err(
-                format!("'{}' does not exist", self.filename())
-            ),
-            FileVerification::BadHash => VerificationResult::Err(
-                format!("'{}' exists but hashes do not match", self.filename())
-            ),
-            _ => VerificationResult::Err(
-                format!("Something went wrong with file '{}'", self.filename())
-            )
-        }
+    async fn get_versions(&self, version_ids: &[String]) -> Result<Vec<VersionInfo>, ModError> {
+        Ok(get_versions_from_list(self, version_ids)
+            .await?
+            .into_iter()
+            .map(VersionInfo::from)
+            .collect()
+        )
     }
-}
-
-impl PartialEq for Mod {
-    fn eq(&self, other: &Self) -> bool {
-        self.project_id == other.project_id
+    async fn resolve_dependency(
+        &self,
+        dep: &DependencyInfo,
+        query: &VersionQuery
+    ) -> Result<VersionInfo, ModError> {
+        let dep = RequiredDependency {
+            version_id: dep.version_id().clone(),
+            project_id: dep.project_id().clone()
+        };
+        Ok(dep.resolve_to_version(self, query).await?.into())
     }
-}
-
-impl PartialEq<String> for Mod {
-    fn eq(&self, other: &String) -> bool {
-        &self.project_id == other
-    }
-}
-
-pub async fn resolve_dependencies(
-    client: &reqwest::Client,
-    query: &VersionQuery,
-    mods: &mut Vec<Mod>,
-) -> Pin<Box<()>>
-{
-    // println!("Func called");
-    let mut deps_to_search: Vec<&RequiredDependency> = Vec::new();
-    let mut new_deps: u16 = 0;
-    for value in &mut *mods {
-        deps_to_search.extend(value.dependencies());
-    }
-    let dep_versions= future::join_all(
-        deps_to_search.iter()
-        .map(|&x| {
-            x.resolve_to_version(client, query)
-        })
-    ).await;
-    for ver_res in dep_versions {
-        if let Ok(ver) = ver_res
-        && !mods.iter().any(|m| m == ver.project_id()) {
-            if let Ok(m) = Mod::build_from_version(client, ver).await {
-                mods.push(m);
-                new_deps += 1;
-            }
-        }
-    };
-    if new_deps > 0 {
-        Box::pin(resolve_dependencies(client, query, mods)).await
-    } else {
-        // println!("No deps found");
-        Box::pin(())
+    fn file_info(&self, version: &VersionInfo) -> Option<FileInfo> {
+        let primary_file_idx = version.files()
+            .iter()
+            .position(|f| f.primary())
+            .unwrap_or(0);
+        version.files().get(primary_file_idx).cloned()
     }
 }
 
@@ -356,6 +128,12 @@ pub struct Project {
     description: String,
 }
 
+impl From<Project> for ProjectInfo {
+    fn from(value: Project) -> Self {
+        ProjectInfo::new(value.id, value.title)
+    }
+}
+
 impl Project {
     pub fn get_id(&self) -> &String {
         &self.id
@@ -374,9 +152,10 @@ pub struct Version {
     project_id: String,
     name: String,
     version_number: String,
+    version_type: String,
+    date_published: String,
     files: Vec<ModrinthFile>,
-    #[serde(deserialize_with = "deserialize_only_required_deps")]
-    dependencies: Vec<RequiredDependency>
+    dependencies: Vec<Dependency>
 }
 
 impl Version {
@@ -392,12 +171,45 @@ impl Version {
     pub fn version_number(&self) -> &String {
         &self.version_number
     }
+    /// `release`, `beta` or `alpha`
+    pub fn version_type(&self) -> &String {
+        &self.version_type
+    }
+    pub fn date_published(&self) -> &String {
+        &self.date_published
+    }
     pub fn files(&self) -> &Vec<ModrinthFile> {
         &self.files
     }
-    pub fn dependencies(&self) -> &Vec<RequiredDependency> {
+    pub fn dependencies(&self) -> &Vec<Dependency> {
         &self.dependencies
     }
+    pub fn required_dependencies(&self) -> Vec<RequiredDependency> {
+        self.dependencies.iter()
+            .filter(|d| d.kind() == DependencyKind::Required)
+            .cloned()
+            .map(RequiredDependency::from_dep)
+            .collect()
+    }
+}
+
+impl From<Version> for VersionInfo {
+    fn from(value: Version) -> Self {
+        VersionInfo::new(
+            value.id,
+            value.project_id,
+            value.name,
+            value.version_number,
+            Some(value.version_type),
+            value.files.into_iter().map(FileInfo::from).collect(),
+            value.dependencies.into_iter()
+                .map(|d| {
+                    let kind = d.kind();
+                    DependencyInfo::new(d.version_id, d.project_id, kind)
+                })
+                .collect()
+        ).with_published(value.date_published)
+    }
 }
 
 impl Clone for Version {
@@ -407,19 +219,42 @@ impl Clone for Version {
             project_id: self.project_id.clone(),
             name: self.name.clone(),
             version_number: self.version_number.clone(),
+            version_type: self.version_type.clone(),
+            date_published: self.date_published.clone(),
             files: self.files.clone(),
             dependencies: self.dependencies.clone()
         }
     }
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Clone)]
 pub struct Dependency {
     version_id: Option<String>,
     project_id: Option<String>,
     dependency_type: String
 }
 
+impl Dependency {
+    pub fn version_id(&self) -> &Option<String> {
+        &self.version_id
+    }
+    pub fn project_id(&self) -> &Option<String> {
+        &self.project_id
+    }
+    pub fn dependency_type(&self) -> &String {
+        &self.dependency_type
+    }
+    /// Unknown types are treated as optional so they never block an install
+    pub fn kind(&self) -> DependencyKind {
+        match self.dependency_type.as_str() {
+            "required" => DependencyKind::Required,
+            "incompatible" => DependencyKind::Incompatible,
+            "embedded" => DependencyKind::Embedded,
+            _ => DependencyKind::Optional
+        }
+    }
+}
+
 pub struct RequiredDependency {
     version_id: Option<String>,
     project_id: Option<String>,
@@ -440,18 +275,15 @@ impl RequiredDependency {
     }
     pub async fn resolve_to_version(
         &self,
-        client: &reqwest::Client,
+        modrinth: &Modrinth,
         query: &VersionQuery
     ) -> Result<Version, ModError>{
         if let Some(v) = &self.version_id {
-            return match get_version_from_version_id(client, v).await {
-                Ok(v) => Ok(v),
-                Err(e) => Err(ModError::BadRequest(e))
-            }
+            return get_version_from_version_id(modrinth, v).await
         } else if let Some(p) = &self.project_id {
-            return get_top_version(client, p, query).await
+            return get_top_version(modrinth, p, query).await
         } else {
-            return Err(ModError::NoDependency("Could not resolve dependency".to_string()))
+            Err(ModError::NoDependency("Could not resolve dependency".to_string()))
         }
     }
 }
@@ -465,30 +297,13 @@ impl Clone for RequiredDependency {
     }
 }
 
-fn deserialize_only_required_deps<'de, D>(
-    deserializer: D
-) -> Result<Vec<RequiredDependency>, D::Error> 
-    where D: Deserializer<'de>
-{
-    let deps: Vec<Dependency> = Deserialize::deserialize(deserializer)?;
-    Ok (deps.into_iter()
-        .filter_map(|d|
-            if d.dependency_type == "required" {
-                Some(RequiredDependency::from_dep(d))
-            } else {
-                None
-            }
-        )
-        .collect()
-    )
-}
-
 #[derive(Deserialize)]
 pub struct ModrinthFile {
     url: String,
     filename: String,
     primary: bool,
     hashes: ModrinthFileHash,
+    size: u64,
 }
 
 impl ModrinthFile {
@@ -501,6 +316,21 @@ impl ModrinthFile {
     pub fn primary(&self) -> &bool {
         &self.primary
     }
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+impl From<ModrinthFile> for FileInfo {
+    fn from(value: ModrinthFile) -> Self {
+        FileInfo::new(
+            value.url,
+            value.filename,
+            value.primary,
+            FileHashes::new(Some(value.hashes.sha512), value.hashes.sha1),
+            Some(value.size)
+        )
+    }
 }
 
 impl Clone for ModrinthFile {
@@ -509,7 +339,8 @@ impl Clone for ModrinthFile {
             url: self.url.clone(),
             filename: self.filename.clone(),
             primary: self.primary,
-            hashes: self.hashes.clone()
+            hashes: self.hashes.clone(),
+            size: self.size
         }
     }
 }
@@ -517,21 +348,16 @@ impl Clone for ModrinthFile {
 #[derive(Deserialize)]
 struct ModrinthFileHash {
     #[serde(deserialize_with = "deserialize_hex_str_to_bytes")]
-    sha512: Vec<u8>
-}
-
-impl ModrinthFileHash {
-    pub fn check512<U>(&self, other_hash: &GenericArray<u8, U>) -> bool
-        where U: ArrayLength<u8>
-    {
-        &self.sha512[..] == &other_hash[..]
-    }
+    sha512: Vec<u8>,
+    #[serde(default, deserialize_with = "deserialize_optional_hex_str")]
+    sha1: Option<Vec<u8>>,
 }
 
 impl Clone for ModrinthFileHash {
     fn clone(&self) -> Self {
         ModrinthFileHash {
-            sha512: self.sha512.clone()
+            sha512: self.sha512.clone(),
+            sha1: self.sha1.clone()
         }
     }
 }
@@ -544,103 +370,116 @@ fn deserialize_hex_str_to_bytes<'de, D>(
     let hex_data: String = Deserialize::deserialize(deserializer)?;
     hex::decode(hex_data).map_err(D::Error::custom)
 }
-#[derive(Serialize)]
-pub struct VersionQuery {
-    game_versions: String,
-    loaders: String
-}
-
-impl VersionQuery {
-    fn build_param_array(user_params: &String) -> String {
-        let mut params = user_params.split(",");
-        let mut res: String = String::from("[");
-        res = format!("{}\"{}\"",
-            res,
-            params.next().unwrap_or(""),
-        );
-        while let Some(prm) = params.next() {
-            res = format!("{},\"{}\"",
-                res,
-                prm,
-            );
-        }
-        format!("{}]", res)
-    }
-    pub fn build_query(user_mcvs: &String, user_loader: &String) -> VersionQuery {
-        let game_versions= Self::build_param_array(user_mcvs);
-        let loaders= Self::build_param_array(user_loader);
-        VersionQuery { game_versions, loaders }
-    }
-    pub fn mcvs(&self) -> &str {
-        &self.game_versions.as_str()
-    }
-    pub fn loader(&self) -> &str {
-        &self.loaders.as_str()
-    }
-}
 
+fn deserialize_optional_hex_str<'de, D>(
+    deserializer: D
+) -> Result<Option<Vec<u8>>, D::Error>
+    where D: Deserializer<'de>
+{
+    deserialize_hex_str_to_bytes(deserializer).map(Some)
+}
 pub async fn get_project(
-    client: &reqwest::Client,
+    modrinth: &Modrinth,
     id: &str
-) -> Result<Project, reqwest::Error>
+) -> Result<Project, ModError>
+{
+    let url = format!("{}{}{}", modrinth.base_url, "/v2/project/", id);
+    let request = modrinth.http.client().get(url);
+    let response = modrinth.http.send(id, request).await?;
+    Ok(response.json::<Project>().await?)
+}
+
+/// `ids` through `/v2/{endpoint}?ids=[...]`, `BULK_CHUNK_SIZE` at a time
+async fn get_bulk<T: DeserializeOwned>(
+    modrinth: &Modrinth,
+    endpoint: &str,
+    ids: &[String]
+) -> Result<Vec<T>, ModError>
 {
-    let url = format!("{}{}{}", MODRINTH_URL, "/v2/project/", id);
-    let response = client.get(url)
-        .send()
-        .await?;
-    response.json::<Project>().await
+    let requests = ids.chunks(BULK_CHUNK_SIZE).map(|chunk| async move {
+        let url = format!("{}/v2/{}", modrinth.base_url, endpoint);
+        let id_array = format!("[{}]", chunk.iter()
+            .map(|id| format!("\"{}\"", id))
+            .collect::<Vec<String>>()
+            .join(",")
+        );
+        let request = modrinth.http.client().get(url)
+            .query(&[("ids", id_array)]);
+        Ok::<Vec<T>, ModError>(modrinth.http.send(endpoint, request).await?.json::<Vec<T>>().await?)
+    });
+    Ok(future::try_join_all(requests).await?.into_iter().flatten().collect())
 }
 
+/// Projects for `ids` in the order asked for; unknown IDs are left out
 pub async fn get_projects_from_list(
-    client: &reqwest::Client,
-    ids: &Vec<String>
-) -> Vec<Result<Project, reqwest::Error>>
+    modrinth: &Modrinth,
+    ids: &[String]
+) -> Result<Vec<Project>, ModError>
 {
-    let mut responses = Vec::new();
-    for id in ids {
-        responses.push(get_project(client, id));
-    }
-    future::join_all(responses).await
+    let mut projects: Vec<Project> = get_bulk(modrinth, "projects", ids).await?;
+    projects.sort_by_key(|p| ids.iter().position(|id| *id == p.id).unwrap_or(ids.len()));
+    Ok(projects)
+}
+
+/// Versions for `ids` in the order asked for; unknown IDs are left out
+pub async fn get_versions_from_list(
+    modrinth: &Modrinth,
+    ids: &[String]
+) -> Result<Vec<Version>, ModError>
+{
+    let mut versions: Vec<Version> = get_bulk(modrinth, "versions", ids).await?;
+    versions.sort_by_key(|v| ids.iter().position(|id| *id == v.id).unwrap_or(ids.len()));
+    Ok(versions)
 }
 
 pub async fn get_version(
-    client: &reqwest::Client,
+    modrinth: &Modrinth,
     project_id: &str,
     query: &VersionQuery
-) -> Result<Vec<Version>, reqwest::Error>
+) -> Result<Vec<Version>, ModError>
 {
     let url = format!("{}{}{}{}",
-        MODRINTH_URL,
+        modrinth.base_url,
         "/v2/project/",
         project_id,
         "/version"
     );
-    let response = client.get(url)
-        .query(query)
-        .send()
-        .await?;
-    response.json::<Vec<Version>>().await
+    let request = modrinth.http.client().get(url)
+        .query(query);
+    let response = modrinth.http.send(project_id, request).await?;
+    Ok(response.json::<Vec<Version>>().await?)
 }
 
 pub async fn get_version_from_version_id(
-    client: &reqwest::Client,
-    id: &String
-) -> Result<Version, reqwest::Error> {
-    let url = format!("{}/v2/version/{}", MODRINTH_URL, id);
-    let response = client.get(url)
-        .send()
-        .await?;
-    response.json::<Version>().await
+    modrinth: &Modrinth,
+    id: &str
+) -> Result<Version, ModError> {
+    let url = format!("{}/v2/version/{}", modrinth.base_url, id);
+    let request = modrinth.http.client().get(url);
+    let response = modrinth.http.send(id, request).await?;
+    Ok(response.json::<Version>().await?)
+}
+
+/// One version of `project_id`, by version ID or version_number
+pub async fn get_project_version(
+    modrinth: &Modrinth,
+    project_id: &str,
+    version: &str
+) -> Result<Version, ModError> {
+    let url = format!("{}/v2/project/{}/version/{}", modrinth.base_url, project_id, version);
+    let request = modrinth.http.client().get(url);
+    let response = modrinth.http.send(&format!("{}@{}", project_id, version), request).await?;
+    Ok(response.json::<Version>().await?)
 }
 
 pub async fn get_top_version(
-    client: & reqwest::Client,
+    modrinth: &Modrinth,
     project_id: &str,
     query: &VersionQuery
 ) -> Result<Version, ModError>
 {
-    let response = get_version(client, project_id, query).await?;
-    match response.get(0).cloned() {
+    let response = get_version(modrinth, project_id, query).await?;
+    match response.first().cloned() {
         Some(v) => Ok(v),
         None => {
             Err(ModError::NoVersionForId(
@@ -650,8 +489,27 @@ pub async fn get_top_version(
     }
 }
 
-pub fn search_for_primary_file(files: &Vec<ModrinthFile>) -> Option<usize> {
-    if files.len() == 0 {
+#[derive(Serialize)]
+struct HashQuery<'a> {
+    hashes: &'a [String],
+    algorithm: &'a str,
+}
+
+/// Looks up installed files by their hex sha512. Hashes Modrinth doesn't
+/// know are missing from the returned map.
+pub async fn get_versions_from_hashes(
+    modrinth: &Modrinth,
+    hashes: &[String]
+) -> Result<HashMap<String, Version>, ModError> {
+    let url = format!("{}/v2/version_files", modrinth.base_url);
+    let request = modrinth.http.client().post(url)
+        .json(&HashQuery { hashes, algorithm: HASH_ALGORITHM });
+    let response = modrinth.http.send("version_files", request).await?;
+    Ok(response.json::<HashMap<String, Version>>().await?)
+}
+
+pub fn search_for_primary_file(files: &[ModrinthFile]) -> Option<usize> {
+    if files.is_empty() {
         return None; // If there are no files
     }
     for (i, file) in files.iter().enumerate() {
@@ -659,199 +517,3 @@ pub fn search_for_primary_file(files: &Vec<ModrinthFile>) -> Option<usize> {
     }
     Some(0) // If no file is marked primary, return 1st file
 }
-
-pub enum FileVerification {
-    Ok,
-    NotExists,
-    BadHash,
-    BadFile
-}
-
-async fn collect_mods(
-    client: & reqwest::Client,
-    ids: &Vec<String>,
-    query: &VersionQuery
-) -> Vec<Mod>
-{
-    let mut mods = Vec::new();
-    for id in ids {
-        mods.push(Mod::build_from_project_id(client, id.to_string(), query));
-    }
-    future::join_all(mods)
-    .await
-    .into_iter()
-    .filter_map(|m| {
-        if let Err(e) = m {
-            println!("{e}");
-            return None
-        } else {
-            return m.ok()
-        }
-    })
-    .collect()
-}
-
-async fn download_mods(
-    client: & reqwest::Client,
-    mods: &Vec<Mod>,
-    out_dir: &PathBuf
-) -> () {
-    let mut download_tasks = Vec::new();
-    for m in mods {
-        download_tasks.push(m.download(client, out_dir));
-    }
-    for e in future::join_all(download_tasks)
-    .await
-    .into_iter()
-    .filter_map(Result::err)
-    .collect::<Vec<DownloadError>>() {
-        println!("{e}");
-    };
-    ()
-}
-
-async fn download_from_id_list<'a>(
-    conf: &arguments::Config<'a>,
-    client: & reqwest::Client,
-    ids: &Vec<String>,
-    out_dir: &PathBuf
-) -> ()
-{
-    let query = VersionQuery::build_query(
-        conf.mcvs(),
-        &conf.loader_as_string()
-    );
-    let mut mods: Vec<Mod> = collect_mods(client, ids, &query).await;
-    if conf.options().get_skip_deps() {
-        println!("[MODRINTH] Skipping dependencies...");
-    } else {
-        println!("[MODRINTH] Getting dependencies...");
-        resolve_dependencies(client, &query, &mut mods).await;
-    }
-    download_mods(client, &mods, out_dir).await;
-    ()
-}
-
-async fn verify_ids_from_list<'a>(
-    conf: &arguments::Config<'a>,
-    client: & reqwest::Client,
-    ids: &Vec<String>,
-    out_dir: &PathBuf
-) -> () {
-    println!(
-        "Checking provided IDs against folder '{}'; dependencies NOT included...",
-        out_dir.display()
-    );
-    let query = VersionQuery::build_query(
-        &conf.mcvs(),
-        &conf.loader_as_string()
-    );
-    let mods: Vec<Mod> = collect_mods(client, ids, &query).await;
-    let mut bad_results: u32 = 0;
-    for m in &mods {
-        let v_res = m.verify(out_dir);
-        if !v_res.is_ok() {
-            bad_results += 1;
-        };
-        v_res.print();
-    };
-    if bad_results > 0 {
-        println!("\n{} out of {} mods were unable to be verified", bad_results, mods.len());
-    } else {
-        println!("All mods verified successfully");
-    };
-}
-
-async fn download_from_id<'a>(
-    conf: &arguments::Config<'a>,
-    client: & reqwest::Client,
-    id: &str,
-    out_dir: &PathBuf
-) -> Result<(), Box<dyn error::Error>>
-{
-    let query = VersionQuery::build_query(
-        conf.mcvs(),
-        &conf.loader_as_string()
-    );
-    let mut mods: Vec<Mod> = Vec::new();
-    mods.push(Mod::build_from_project_id(
-        client, 
-        id.to_string(), 
-        &query
-    ).await?);
-    if conf.options().get_skip_deps() {
-        println!("[MODRINTH] Skipping dependencies...");
-    } else {
-        println!("[MODRINTH] Getting dependencies...");
-        resolve_dependencies(client, &query, &mut mods).await;
-    }
-    download_mods(client, &mods, out_dir).await;
-    Ok(())
-}
-
-async fn verify_id<'a> (
-    conf: &arguments::Config<'a>,
-    client: & reqwest::Client,
-    id: &str,
-    out_dir: &PathBuf
-) -> Result<(), ModError> {
-    let query = VersionQuery::build_query(
-        &conf.mcvs(),
-        &conf.loader_as_string()
-    );
-    let m = Mod::build_from_project_id(
-        client, 
-        id.to_string(), 
-        &query
-    ).await?;
-    m.verify(out_dir).print();
-    Ok(())
-}
-
-pub async fn handle_list_input<'a>(
-    conf: &arguments::Config<'a>,
-    client: &reqwest::Client,
-    id_list: &Vec<String>,
-    out_dir: &PathBuf
-) -> Result<(), Box<dyn error::Error>> {
-    if conf.options().get_verify() {
-        verify_ids_from_list(
-            conf,
-            client,
-            id_list,
-            out_dir
-        ).await;
-    } else {
-        download_from_id_list(
-            conf,
-            client,
-            id_list,
-            out_dir
-        ).await;
-    };
-    Ok(())
-}
-
-pub async fn handle_single_input<'a>(
-    conf: &arguments::Config<'a>,
-    client: &reqwest::Client,
-    id: &str,
-    out_dir: &PathBuf
-) -> Result<(), Box<dyn error::Error>> {
-    if conf.options().get_verify() {
-        verify_id(
-            conf,
-            client,
-            id,
-            out_dir
-        ).await?;
-    } else {
-        download_from_id(
-            conf,
-            client,
-            id,
-            out_dir
-        ).await?;
-    };
-    Ok(())
-}
\ No newline at end of file
@@ -0,0 +1,418 @@
+use std::collections::BTreeMap;
+use std::{fmt, fs, io, error};
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use futures::future;
+use serde::{Deserialize, Serialize};
+use zip::{ZipArchive, ZipWriter};
+use zip::write::SimpleFileOptions;
+
+use crate::arguments::Side;
+use crate::pipeline::{self, DownloadError, Mod};
+use crate::provider::{FileDigest, FileHashes};
+
+pub const INDEX_NAME: &str = "modrinth.index.json";
+const FORMAT_VERSION: u32 = 1;
+const GAME: &str = "minecraft";
+const OVERRIDES: &str = "overrides";
+const CLIENT_OVERRIDES: &str = "client-overrides";
+const SERVER_OVERRIDES: &str = "server-overrides";
+
+#[derive(Debug)]
+pub enum PackError {
+    BadFile(io::Error),
+    BadArchive(zip::result::ZipError),
+    BadIndex(serde_json::Error),
+    UnsafePath(String),
+    BadEntry(String),
+    Incomplete(usize, usize),
+}
+
+impl fmt::Display for PackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BadFile(err) => write!(f, "[MRPACK/ERROR] Bad file: {}", err),
+            Self::BadArchive(err) => write!(f, "[MRPACK/ERROR] Bad archive: {}", err),
+            Self::BadIndex(err) => write!(f, "[MRPACK/ERROR] Bad {}: {}", INDEX_NAME, err),
+            Self::UnsafePath(path) => write!(f, "[MRPACK/ERROR] Refusing to write outside the target folder: {}", path),
+            Self::BadEntry(msg) => write!(f, "[MRPACK/ERROR] Bad file entry: {}", msg),
+            Self::Incomplete(failed, total) => write!(f, "[MRPACK/ERROR] {} out of {} pack files could not be installed", failed, total),
+        }
+    }
+}
+
+impl error::Error for PackError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::BadFile(err) => Some(err),
+            Self::BadArchive(err) => Some(err),
+            Self::BadIndex(err) => Some(err),
+            _ => None
+        }
+    }
+}
+
+impl From<io::Error> for PackError {
+    fn from(value: io::Error) -> Self {
+        Self::BadFile(value)
+    }
+}
+
+impl From<zip::result::ZipError> for PackError {
+    fn from(value: zip::result::ZipError) -> Self {
+        Self::BadArchive(value)
+    }
+}
+
+impl From<serde_json::Error> for PackError {
+    fn from(value: serde_json::Error) -> Self {
+        Self::BadIndex(value)
+    }
+}
+
+/// Contents of `modrinth.index.json`
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PackIndex {
+    format_version: u32,
+    game: String,
+    version_id: String,
+    name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    summary: Option<String>,
+    files: Vec<PackFile>,
+    /// `minecraft` plus the loader, e.g. `fabric-loader`
+    dependencies: BTreeMap<String, String>,
+}
+
+impl PackIndex {
+    pub fn build(
+        name: String,
+        version_id: String,
+        game_version: &str,
+        loader: (&str, &str),
+        files: Vec<PackFile>
+    ) -> Self {
+        let mut dependencies = BTreeMap::new();
+        dependencies.insert(GAME.to_string(), game_version.to_string());
+        dependencies.insert(loader.0.to_string(), loader.1.to_string());
+        PackIndex {
+            format_version: FORMAT_VERSION,
+            game: GAME.to_string(),
+            version_id,
+            name,
+            summary: None,
+            files,
+            dependencies
+        }
+    }
+    /// Writes the index to a new pack at `pack_path`, with the contents of
+    /// `config_dir` as `overrides/config`. Returns the number of bundled files.
+    pub fn write(&self, pack_path: &Path, config_dir: Option<&Path>) -> Result<u32, PackError> {
+        let mut zip = ZipWriter::new(fs::File::create(pack_path)?);
+        zip.start_file(INDEX_NAME, SimpleFileOptions::default())?;
+        serde_json::to_writer_pretty(&mut zip, self)?;
+        let bundled = match config_dir {
+            Some(dir) => add_folder(&mut zip, dir, &format!("{}/config", OVERRIDES))?,
+            None => 0
+        };
+        zip.finish()?;
+        Ok(bundled)
+    }
+    pub fn format_version(&self) -> u32 {
+        self.format_version
+    }
+    pub fn game(&self) -> &String {
+        &self.game
+    }
+    pub fn version_id(&self) -> &String {
+        &self.version_id
+    }
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+    pub fn summary(&self) -> &Option<String> {
+        &self.summary
+    }
+    pub fn files(&self) -> &Vec<PackFile> {
+        &self.files
+    }
+    pub fn dependencies(&self) -> &BTreeMap<String, String> {
+        &self.dependencies
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PackFile {
+    path: String,
+    hashes: PackHashes,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    env: Option<PackEnv>,
+    downloads: Vec<String>,
+    file_size: u64,
+}
+
+impl PackFile {
+    /// Entry for `m` under `mods/`. A hash or size the provider didn't
+    /// report (CurseForge, lockfiles) is taken from the copy in `out_dir`.
+    pub fn from_mod(m: &Mod, out_dir: &Path) -> Result<Self, PackError> {
+        let file = m.file();
+        let hashes = file.hashes();
+        let (sha1, sha512, file_size) = match (hashes.sha1(), hashes.sha512(), file.size()) {
+            (Some(sha1), Some(sha512), Some(size)) => (hex::encode(sha1), hex::encode(sha512), size),
+            _ => {
+                let digest = FileDigest::of_file(&out_dir.join(m.filename())).map_err(|_| PackError::BadEntry(
+                    format!("'{}' is missing hashes and is not installed in '{}'", m.filename(), out_dir.display())
+                ))?;
+                if !hashes.matches(&digest) {
+                    return Err(PackError::BadEntry(
+                        format!("Installed copy of '{}' does not match its hashes", m.filename())
+                    ));
+                }
+                (
+                    hex::encode(digest.sha1()),
+                    hex::encode(digest.sha512()),
+                    digest.size()
+                )
+            }
+        };
+        Ok(PackFile {
+            path: format!("mods/{}", m.filename()),
+            hashes: PackHashes::new(sha1, sha512),
+            env: None,
+            downloads: vec![file.url().clone()],
+            file_size
+        })
+    }
+    pub fn path(&self) -> &String {
+        &self.path
+    }
+    pub fn downloads(&self) -> &Vec<String> {
+        &self.downloads
+    }
+    pub fn file_size(&self) -> u64 {
+        self.file_size
+    }
+    /// Files without an `env` block are needed on both sides
+    pub fn support_on(&self, side: &Side) -> EnvSupport {
+        match (&self.env, side) {
+            (Some(env), Side::Client) => env.client,
+            (Some(env), Side::Server) => env.server,
+            (None, _) => EnvSupport::Required
+        }
+    }
+    pub fn file_hashes(&self) -> Result<FileHashes, PackError> {
+        let decode = |hash: &str| hex::decode(hash).map_err(|_| PackError::BadEntry(
+            format!("Invalid hash for '{}'", self.path)
+        ));
+        Ok(FileHashes::new(
+            Some(decode(&self.hashes.sha512)?),
+            Some(decode(&self.hashes.sha1)?)
+        ))
+    }
+    async fn download(
+        &self,
+        client: &reqwest::Client,
+        hashes: &FileHashes,
+        file_path: &Path
+    ) -> Result<(), DownloadError> {
+        if let Ok(true) = hashes.check_file(file_path) {
+            println!("[MRPACK] {} already present. Skipping download...", self.path);
+            return Ok(());
+        }
+        if let Some(parent) = file_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        println!("[MRPACK] Downloading {}", self.path);
+        let mut last_err = DownloadError::BadHash(format!("No download for '{}'", self.path));
+        // Later URLs are mirrors of the first
+        for url in &self.downloads {
+            match pipeline::download_verified(client, url, hashes, file_path).await {
+                Ok(true) => {
+                    println!("[MRPACK] Successfully downloaded {}", self.path);
+                    return Ok(());
+                },
+                Ok(false) => last_err = DownloadError::BadHash(
+                    format!("Hashes do not match for '{}' from {}", self.path, url)
+                ),
+                Err(e) => last_err = e
+            }
+        }
+        Err(last_err)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PackHashes {
+    sha1: String,
+    sha512: String,
+}
+
+impl PackHashes {
+    pub fn new(sha1: String, sha512: String) -> Self {
+        PackHashes { sha1, sha512 }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PackEnv {
+    client: EnvSupport,
+    server: EnvSupport,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum EnvSupport {
+    Required,
+    Optional,
+    Unsupported,
+}
+
+pub struct Modpack {
+    archive: ZipArchive<fs::File>,
+    index: PackIndex,
+}
+
+impl Modpack {
+    pub fn open(pack_path: &Path) -> Result<Self, PackError> {
+        let mut archive = ZipArchive::new(fs::File::open(pack_path)?)?;
+        let index: PackIndex = serde_json::from_reader(archive.by_name(INDEX_NAME)?)?;
+        Ok(Modpack { archive, index })
+    }
+    pub fn index(&self) -> &PackIndex {
+        &self.index
+    }
+    pub fn print_info(&self) {
+        println!("[MRPACK] {} ({})", self.index.name, self.index.version_id);
+        for (dep, ver) in &self.index.dependencies {
+            println!("[MRPACK]   {dep} {ver}");
+        }
+    }
+    /// Copies `overrides/` into `root`, then the folder for `side` over it.
+    /// Files that already hold the same bytes are left alone. Returns the
+    /// number of files written.
+    pub fn extract_overrides(&mut self, root: &Path, side: &Side) -> Result<u32, PackError> {
+        let side_folder = match side {
+            Side::Client => CLIENT_OVERRIDES,
+            Side::Server => SERVER_OVERRIDES
+        };
+        let mut files: BTreeMap<PathBuf, Vec<u8>> = BTreeMap::new();
+        for folder in [OVERRIDES, side_folder] {
+            for i in 0..self.archive.len() {
+                let mut entry = self.archive.by_index(i)?;
+                let Some(name) = entry.enclosed_name() else {
+                    return Err(PackError::UnsafePath(entry.name()?.to_string()));
+                };
+                let Ok(rel) = name.strip_prefix(folder) else { continue };
+                if rel.as_os_str().is_empty() {
+                    continue;
+                }
+                let out_path = root.join(rel);
+                if entry.is_dir() {
+                    fs::create_dir_all(&out_path)?;
+                    continue;
+                }
+                let mut bytes: Vec<u8> = Vec::new();
+                entry.read_to_end(&mut bytes)?;
+                files.insert(out_path, bytes);
+            }
+        }
+        let mut written: u32 = 0;
+        for (out_path, bytes) in files {
+            if fs::read(&out_path).is_ok_and(|existing| existing == bytes) {
+                continue;
+            }
+            if let Some(parent) = out_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            // Replace rather than write through, in case the file is a
+            // hard link into an installed folder
+            if out_path.exists() {
+                fs::remove_file(&out_path)?;
+            }
+            fs::write(&out_path, bytes)?;
+            written += 1;
+        }
+        Ok(written)
+    }
+}
+
+/// Dependency key the index uses for `loader`
+pub fn loader_dependency(loader: &str) -> Option<&'static str> {
+    match loader {
+        "fabric" => Some("fabric-loader"),
+        "quilt" => Some("quilt-loader"),
+        "forge" => Some("forge"),
+        "neoforge" => Some("neoforge"),
+        _ => None
+    }
+}
+
+fn add_folder(
+    zip: &mut ZipWriter<fs::File>,
+    dir: &Path,
+    name: &str
+) -> Result<u32, PackError> {
+    let mut entries = fs::read_dir(dir)?.collect::<Result<Vec<fs::DirEntry>, io::Error>>()?;
+    entries.sort_by_key(|e| e.file_name());
+    let mut added: u32 = 0;
+    for entry in entries {
+        let entry_name = format!("{}/{}", name, entry.file_name().to_string_lossy());
+        if entry.file_type()?.is_dir() {
+            added += add_folder(zip, &entry.path(), &entry_name)?;
+        } else {
+            zip.start_file(entry_name, SimpleFileOptions::default())?;
+            io::copy(&mut fs::File::open(entry.path())?, zip)?;
+            added += 1;
+        }
+    }
+    Ok(added)
+}
+
+/// Joins a pack-relative path onto `root`, rejecting anything that could
+/// escape it
+pub fn pack_path(root: &Path, path: &str) -> Result<PathBuf, PackError> {
+    let rel = Path::new(path);
+    if path.is_empty() || !rel.components().all(|c| matches!(c, Component::Normal(_))) {
+        return Err(PackError::UnsafePath(path.to_string()));
+    }
+    Ok(root.join(rel))
+}
+
+/// Downloads every file in `index` needed on `side` into `root`. Entries
+/// are all validated before anything is downloaded.
+pub async fn install_files(
+    client: &reqwest::Client,
+    index: &PackIndex,
+    side: &Side,
+    root: &Path
+) -> Result<(), PackError> {
+    let mut to_install = Vec::new();
+    for file in index.files() {
+        match file.support_on(side) {
+            EnvSupport::Unsupported => {
+                println!("[MRPACK] Skipping {} (not used on this side)", file.path);
+                continue;
+            }
+            EnvSupport::Optional => println!("[MRPACK] Including optional file {}", file.path),
+            EnvSupport::Required => ()
+        }
+        if file.downloads.is_empty() {
+            return Err(PackError::BadEntry(format!("No downloads for '{}'", file.path)));
+        }
+        to_install.push((file, file.file_hashes()?, pack_path(root, &file.path)?));
+    }
+    let results = future::join_all(
+        to_install.iter().map(|(file, hashes, path)| file.download(client, hashes, path))
+    ).await;
+    let mut failed: usize = 0;
+    for e in results.into_iter().filter_map(Result::err) {
+        println!("{e}");
+        failed += 1;
+    }
+    if failed > 0 {
+        return Err(PackError::Incomplete(failed, to_install.len()));
+    }
+    Ok(())
+}
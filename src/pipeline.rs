@@ -0,0 +1,1141 @@
+use std::{fmt, fs, error};
+use std::io::{self, Write};
+use std::mem;
+use std::path::{self, Path};
+use futures::{future, stream, StreamExt};
+
+use crate::arguments;
+use crate::cache::CachedProvider;
+use crate::file_parse::ManifestEntry;
+use crate::graph::DependencyGraph;
+use crate::lockfile::{self, Lockfile, LockedHashes, LockedMod};
+use crate::provider::{
+    DependencyInfo,
+    DependencyKind,
+    Digester,
+    FileDigest,
+    FileHashes,
+    FileInfo,
+    ModError,
+    ModProvider,
+    ProjectInfo,
+    VersionInfo,
+    VersionQuery
+};
+use crate::staging::Staging;
+
+/// Most provider requests one resolution keeps in flight at once
+const MAX_CONCURRENT_REQUESTS: usize = 8;
+
+#[derive(Debug)]
+pub enum DownloadError {
+    BadRequest(reqwest::Error),
+    BadFile(io::Error),
+    BadHash(String),
+    Incomplete(usize, usize),
+}
+
+impl fmt::Display for DownloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BadRequest(err) => write!(f, "[DOWNLOAD/ERROR] Bad request: {}", err),
+            Self::BadFile(err) => write!(f, "[DOWNLOAD/ERROR] Bad file: {}", err),
+            Self::BadHash(msg) => write!(f, "[DOWNLOAD/ERROR] Bad hash: {}", msg),
+            Self::Incomplete(failed, total) => write!(f, "[DOWNLOAD/ERROR] {} out of {} mods failed to download", failed, total),
+        }
+    }
+}
+
+impl error::Error for DownloadError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::BadRequest(err) => Some(err),
+            Self::BadFile(err) => Some(err),
+            _ => None
+        }
+    }
+}
+
+impl From<reqwest::Error> for DownloadError {
+    fn from(value: reqwest::Error) -> Self {
+        Self::BadRequest(value)
+    }
+}
+
+impl From<std::io::Error> for DownloadError {
+    fn from(value: std::io::Error) -> Self {
+        Self::BadFile(value)
+    }
+}
+
+pub enum VerificationResult {
+    Ok(String),
+    Err(String)
+}
+
+impl VerificationResult {
+    pub fn print(&self) {
+        match self {
+            Self::Ok(v) => {
+                println!("[VERIFY] {v}")
+            }
+            Self::Err(e) => {
+                println!("[VERIFY/ERROR] {e}")
+            }
+        }
+    }
+    pub fn is_ok(&self) -> bool {
+        matches!(self, Self::Ok(_))
+    }
+}
+
+pub struct Mod {
+    title: String,
+    project_id: String,
+    version_name: String,
+    version_id: String,
+    file: FileInfo,
+    dependencies: Vec<DependencyInfo>,
+    with_optional: bool,
+}
+
+impl Mod {
+    pub fn title(&self) -> &String {
+        &self.title
+    }
+    pub fn project_id(&self) -> &String {
+        &self.project_id
+    }
+    pub fn version_name(&self) -> &String {
+        &self.version_name
+    }
+    pub fn version_id(&self) -> &String {
+        &self.version_id
+    }
+    pub fn filename(&self) -> &String {
+        self.file.filename()
+    }
+    pub fn file(&self) -> &FileInfo {
+        &self.file
+    }
+    pub fn dependencies(&self) -> &Vec<DependencyInfo> {
+        &self.dependencies
+    }
+    /// Dependencies to install: the required ones, plus optional ones this
+    /// mod's manifest entry or `optional` asks for
+    pub fn wanted_dependencies(&self, optional: &arguments::OptionalDeps) -> Vec<&DependencyInfo> {
+        self.dependencies.iter()
+            .filter(|d| match d.kind() {
+                DependencyKind::Required => true,
+                DependencyKind::Optional => self.with_optional || optional.includes(d.project_id()),
+                _ => false
+            })
+            .collect()
+    }
+    /// `wanted_dependencies`, each paired with this mod's project ID
+    fn queued_dependencies(&self, optional: &arguments::OptionalDeps) -> Vec<(String, DependencyInfo)> {
+        self.wanted_dependencies(optional)
+            .into_iter()
+            .map(|d| (self.project_id.clone(), d.clone()))
+            .collect()
+    }
+    fn build<P: ModProvider>(
+        provider: &P,
+        proj: ProjectInfo,
+        ver: VersionInfo,
+    ) -> Result<Self, ModError> {
+        let file = provider.file_info(&ver)
+        .ok_or(ModError::NoFileForProj(
+            format!("Couldn't find file for project {}", proj.get_title())
+        ))?;
+        println!("[{}] Found mod '{}' for id '{}'", provider.tag(), proj.get_title(), proj.get_id());
+        Ok(Mod {
+            title: proj.get_title().clone(),
+            project_id: proj.get_id().clone(),
+            version_name: ver.name().clone(),
+            version_id: ver.id().clone(),
+            file,
+            dependencies: ver.dependencies().clone(),
+            with_optional: false
+        })
+    }
+    pub async fn build_from_project_id<P: ModProvider>(
+        provider: &P,
+        project_id: String,
+        query: &VersionQuery
+    ) -> Result<Self, ModError> {
+        println!("[{}] Searching for project id '{}'", provider.tag(), project_id);
+        let proj = provider.resolve_project(&project_id).await?;
+        let top_version = provider.list_versions(proj.get_id(), query)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or(ModError::NoVersionForId(project_id))?;
+        Self::build(provider, proj, top_version)
+    }
+    pub async fn build_from_version_id<P: ModProvider>(
+        provider: &P,
+        project_id: Option<&str>,
+        version_id: String,
+    ) -> Result<Self, ModError> {
+        println!("[{}] Searching for version id '{}'", provider.tag(), version_id);
+        let ver = provider.get_version(project_id, &version_id).await?;
+        let proj = provider.resolve_project(ver.project_id()).await?;
+        Self::build(provider, proj, ver)
+    }
+    /// The version pinned by `entry`, or else the newest one meeting its
+    /// constraints
+    pub async fn build_from_entry<P: ModProvider>(
+        provider: &P,
+        entry: &ManifestEntry,
+        query: &VersionQuery
+    ) -> Result<Self, ModError> {
+        if let Some(version_id) = entry.version_id() {
+            let mut m = Self::build_from_version_id(provider, Some(entry.id()), version_id.clone()).await?;
+            m.with_optional = entry.with_optional();
+            return Ok(m);
+        }
+        println!("[{}] Searching for project id '{}'", provider.tag(), entry.id());
+        let proj = provider.resolve_project(entry.id()).await?;
+        let version = provider.list_versions(proj.get_id(), &entry.query(query))
+            .await?
+            .into_iter()
+            .find(|v| entry.accepts(v))
+            .ok_or(ModError::NoVersionForId(
+                format!("{} (nothing matches the manifest constraints)", entry.id())
+            ))?;
+        let mut m = Self::build(provider, proj, version)?;
+        m.with_optional = entry.with_optional();
+        Ok(m)
+    }
+    pub async fn build_from_version<P: ModProvider>(
+        provider: &P,
+        ver: VersionInfo
+    ) -> Result<Self, ModError> {
+        println!("[{}] Using version id '{}'", provider.tag(), ver.id());
+        let proj = provider.resolve_project(ver.project_id()).await?;
+        Self::build(provider, proj, ver)
+    }
+    pub fn from_lock_entry(entry: &LockedMod) -> Result<Self, ModError> {
+        let decode = |hash: &Option<String>| -> Result<Option<Vec<u8>>, ModError> {
+            match hash {
+                Some(h) => hex::decode(h).map(Some).map_err(|_| ModError::BadLockEntry(
+                    format!("Invalid hash for '{}'", entry.filename())
+                )),
+                None => Ok(None)
+            }
+        };
+        let hashes = FileHashes::new(decode(entry.sha512())?, decode(entry.sha1())?);
+        Ok(Mod {
+            title: entry.title().clone(),
+            project_id: entry.project_id().clone(),
+            version_name: entry.version_name().clone(),
+            version_id: entry.version_id().clone(),
+            file: FileInfo::new(
+                entry.url().clone(),
+                entry.filename().clone(),
+                true,
+                hashes,
+                None
+            ),
+            dependencies: Vec::new(),
+            with_optional: false
+        })
+    }
+    pub fn lock_entry(&self) -> LockedMod {
+        let hashes = self.file.hashes();
+        LockedMod::new(
+            self.title.clone(),
+            self.project_id.clone(),
+            self.version_id.clone(),
+            self.version_name.clone(),
+            self.file.filename().clone(),
+            self.file.url().clone(),
+            LockedHashes::new(
+                hashes.sha512().as_ref().map(hex::encode),
+                hashes.sha1().as_ref().map(hex::encode)
+            )
+        )
+    }
+    pub fn verify_against(&self, file_path: &Path) -> FileVerification {
+        if !path::Path::exists(file_path) {
+            return FileVerification::NotExists
+        }
+        match self.file.hashes().check_file(file_path) {
+            Ok(true) => FileVerification::Ok,
+            Ok(false) => FileVerification::BadHash,
+            Err(_) => FileVerification::BadFile
+        }
+    }
+    pub async fn download(
+        &self,
+        client: &reqwest::Client,
+        out_dir: &Path
+    ) -> Result<(), DownloadError> {
+        let file_path = out_dir.join(self.filename());
+        match self.verify_against(&file_path){
+            FileVerification::Ok => {
+                println!("[DOWNLOAD] {} already present. Skipping download...", self.title());
+                return Ok(());
+            }
+            FileVerification::BadHash => {
+                println!("[DOWNLOAD/WARNING] File present for {}, but hashes do not match. Continuing with download...", self.title());
+            }
+            FileVerification::BadFile => {
+                println!("[DOWNLOAD/WARNING] File present for {}, but something is wrong. Continuing with download...", self.title());
+            }
+            FileVerification::NotExists => {
+                println!("[DOWNLOAD] Downloading file {} for {}", self.file.filename(), self.title());
+            }
+        }
+        if download_verified(client, self.file.url(), self.file.hashes(), &file_path).await? {
+            println!("[DOWNLOAD] Successfully downloaded {}", self.file.filename());
+            Ok(())
+        } else {
+            Err(DownloadError::BadHash(
+                format!("Hashes do not match for file '{}'", self.file.filename())
+            ))
+        }
+    }
+    /// `download`, tried up to `retries` more times when the request fails
+    /// or the file doesn't match its hashes
+    pub async fn download_with_retries(
+        &self,
+        client: &reqwest::Client,
+        out_dir: &Path,
+        retries: u32
+    ) -> Result<(), DownloadError> {
+        let mut attempt: u32 = 0;
+        loop {
+            match self.download(client, out_dir).await {
+                Err(e @ (DownloadError::BadHash(_) | DownloadError::BadRequest(_))) if attempt < retries => {
+                    attempt += 1;
+                    println!("{e}");
+                    println!("[DOWNLOAD] Retrying {} (attempt {} of {})", self.filename(), attempt, retries);
+                }
+                result => return result
+            }
+        }
+    }
+    fn verify(
+        &self,
+        out_dir: &Path
+    ) -> VerificationResult
+    {
+        let file_path = out_dir.join(self.filename());
+        match self.verify_against(&file_path) {
+            FileVerification::Ok => VerificationResult::Ok(
+                format!("Successfully verified '{}'", self.filename())
+            ),
+            FileVerification::NotExists => VerificationResult::Err(
+                format!("'{}' does not exist", self.filename())
+            ),
+            FileVerification::BadHash => VerificationResult::Err(
+                format!("'{}' exists but hashes do not match", self.filename())
+            ),
+            _ => VerificationResult::Err(
+                format!("Something went wrong with file '{}'", self.filename())
+            )
+        }
+    }
+}
+
+/// Streams `url` into a temporary file next to `file_path`, hashing it on
+/// the way, and moves it into place only if it matches `hashes`. Returns
+/// whether it did; a mismatched download is deleted.
+pub async fn download_verified(
+    client: &reqwest::Client,
+    url: &str,
+    hashes: &FileHashes,
+    file_path: &Path
+) -> Result<bool, DownloadError> {
+    let filename = file_path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let part_path = file_path.with_file_name(format!(".{}.part", filename));
+    let digest = match stream_to_file(client, url, &part_path).await {
+        Ok(d) => d,
+        Err(e) => {
+            let _ = fs::remove_file(&part_path);
+            return Err(e);
+        }
+    };
+    if hashes.matches(&digest) {
+        fs::rename(&part_path, file_path)?;
+        Ok(true)
+    } else {
+        fs::remove_file(&part_path)?;
+        Ok(false)
+    }
+}
+
+async fn stream_to_file(
+    client: &reqwest::Client,
+    url: &str,
+    file_path: &Path
+) -> Result<FileDigest, DownloadError> {
+    let mut response = client.get(url)
+        .send()
+        .await?
+        .error_for_status()?;
+    let mut f_out = io::BufWriter::new(fs::File::create(file_path)?);
+    let mut digester = Digester::new();
+    while let Some(chunk) = response.chunk().await? {
+        digester.update(&chunk);
+        f_out.write_all(&chunk)?;
+    }
+    f_out.flush()?;
+    Ok(digester.finish())
+}
+
+impl PartialEq for Mod {
+    fn eq(&self, other: &Self) -> bool {
+        self.project_id == other.project_id
+    }
+}
+
+impl PartialEq<String> for Mod {
+    fn eq(&self, other: &String) -> bool {
+        &self.project_id == other
+    }
+}
+
+pub async fn resolve_dependencies<P: ModProvider>(
+    provider: &P,
+    query: &VersionQuery,
+    mods: &mut Vec<Mod>,
+) -> DependencyGraph
+{
+    resolve_dependencies_with(provider, query, mods, &arguments::OptionalDeps::None).await
+}
+
+/// Like `resolve_dependencies`, also pulling in the optional dependencies
+/// `optional` names. The mods already in `mods` become the graph's roots.
+///
+/// Works through a queue: each mod's dependencies are resolved once, when
+/// the mod joins the set, with at most `MAX_CONCURRENT_REQUESTS` in flight.
+pub async fn resolve_dependencies_with<P: ModProvider>(
+    provider: &P,
+    query: &VersionQuery,
+    mods: &mut Vec<Mod>,
+    optional: &arguments::OptionalDeps
+) -> DependencyGraph
+{
+    let mut graph = roots_graph(mods);
+    let pending: Vec<(String, DependencyInfo)> = mods.iter()
+        .flat_map(|m| m.queued_dependencies(optional))
+        .collect();
+    work_through(provider, query, mods, optional, pending, &mut graph).await;
+    graph
+}
+
+/// Resolves `pending` and everything it pulls in, adding new mods to
+/// `mods` and their edges to `graph`
+async fn work_through<P: ModProvider>(
+    provider: &P,
+    query: &VersionQuery,
+    mods: &mut Vec<Mod>,
+    optional: &arguments::OptionalDeps,
+    mut pending: Vec<(String, DependencyInfo)>,
+    graph: &mut DependencyGraph
+) {
+    while !pending.is_empty() {
+        // Pinned versions and the projects of new mods are looked up in bulk
+        let pinned_ids: Vec<String> = pending.iter()
+            .filter_map(|(_, d)| d.version_id().clone())
+            .collect();
+        let pinned: Vec<VersionInfo> = if pinned_ids.is_empty() {
+            Vec::new()
+        } else {
+            provider.get_versions(&pinned_ids).await.unwrap_or_default()
+        };
+        let pinned = &pinned;
+        let resolved: Vec<(String, Result<VersionInfo, ModError>)> = stream::iter(mem::take(&mut pending))
+            .map(|(parent, dep)| async move {
+                let known = pinned.iter().find(|v| Some(v.id()) == dep.version_id().as_ref());
+                let ver = match known {
+                    Some(v) => Ok(v.clone()),
+                    None => provider.resolve_dependency(&dep, query).await
+                };
+                (parent, ver)
+            })
+            .buffered(MAX_CONCURRENT_REQUESTS)
+            .collect()
+            .await;
+        let mut edges: Vec<(String, String)> = Vec::new();
+        let mut new_versions: Vec<VersionInfo> = Vec::new();
+        for (parent, ver_res) in resolved {
+            let Ok(ver) = ver_res else { continue };
+            edges.push((parent, ver.project_id().clone()));
+            if !mods.iter().any(|m| m == ver.project_id())
+            && !new_versions.iter().any(|v| v.project_id() == ver.project_id()) {
+                new_versions.push(ver);
+            }
+        }
+        let project_ids: Vec<String> = new_versions.iter().map(|v| v.project_id().clone()).collect();
+        let projects: Vec<ProjectInfo> = if project_ids.is_empty() {
+            Vec::new()
+        } else {
+            provider.resolve_projects(&project_ids).await.unwrap_or_default()
+        };
+        let projects = &projects;
+        let built: Vec<Result<Mod, ModError>> = stream::iter(new_versions)
+            .map(|ver| async move {
+                match projects.iter().find(|p| p.get_id() == ver.project_id()) {
+                    Some(proj) => Mod::build(provider, proj.clone(), ver),
+                    None => Mod::build_from_version(provider, ver).await
+                }
+            })
+            .buffered(MAX_CONCURRENT_REQUESTS)
+            .collect()
+            .await;
+        for m in built.into_iter().filter_map(Result::ok) {
+            graph.add_node(&m.project_id, &m.title);
+            pending.extend(m.queued_dependencies(optional));
+            mods.push(m);
+        }
+        for (parent, child) in edges {
+            if mods.iter().any(|m| m == &child) {
+                graph.add_edge(&parent, &child);
+            }
+        }
+    }
+}
+
+/// One version of a conflicting project and the mods pinning it
+pub struct VersionCandidate {
+    version: VersionInfo,
+    required_by: Vec<String>,
+}
+
+impl VersionCandidate {
+    pub fn version(&self) -> &VersionInfo {
+        &self.version
+    }
+    pub fn required_by(&self) -> &Vec<String> {
+        &self.required_by
+    }
+}
+
+/// Mods pin different versions of the same project. `selected` is the
+/// version the resolver kept, which came first.
+pub struct VersionConflict {
+    title: String,
+    project_id: String,
+    selected: String,
+    candidates: Vec<VersionCandidate>,
+}
+
+impl VersionConflict {
+    pub fn title(&self) -> &String {
+        &self.title
+    }
+    pub fn project_id(&self) -> &String {
+        &self.project_id
+    }
+    pub fn selected(&self) -> &String {
+        &self.selected
+    }
+    pub fn candidates(&self) -> &Vec<VersionCandidate> {
+        &self.candidates
+    }
+    /// The most recently published candidate; unknown dates lose
+    pub fn newest(&self) -> Option<&VersionInfo> {
+        self.candidates.iter()
+            .map(|c| &c.version)
+            .max_by(|a, b| a.published().cmp(b.published()))
+    }
+}
+
+impl fmt::Display for VersionConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let candidates: Vec<String> = self.candidates.iter()
+            .map(|c| {
+                let mut text = c.version.version_number().clone();
+                if !c.required_by.is_empty() {
+                    text = format!("{} required by {}", text, c.required_by.join(", "));
+                }
+                if c.version.id() == &self.selected {
+                    text = format!("{} [selected]", text);
+                }
+                text
+            })
+            .collect();
+        write!(f, "{} is pinned to different versions: {}", self.title, candidates.join("; "))
+    }
+}
+
+/// Projects that the set's dependencies pin to a version other than the
+/// one that was resolved
+pub async fn find_version_conflicts<P: ModProvider>(
+    provider: &P,
+    query: &VersionQuery,
+    mods: &[Mod],
+    optional: &arguments::OptionalDeps
+) -> Vec<VersionConflict>
+{
+    let pins: Vec<(String, DependencyInfo)> = mods.iter()
+        .flat_map(|m| m.wanted_dependencies(optional)
+            .into_iter()
+            .filter(|d| d.version_id().is_some())
+            .map(|d| (m.title.clone(), d.clone()))
+        )
+        .collect();
+    let resolved: Vec<(String, Result<VersionInfo, ModError>)> = stream::iter(pins)
+        .map(|(title, dep)| async move {
+            let ver = provider.resolve_dependency(&dep, query).await;
+            (title, ver)
+        })
+        .buffered(MAX_CONCURRENT_REQUESTS)
+        .collect()
+        .await;
+    let mut by_project: Vec<(String, Vec<VersionCandidate>)> = Vec::new();
+    for (title, ver_res) in resolved {
+        let Ok(ver) = ver_res else { continue };
+        let idx = match by_project.iter().position(|(p, _)| p == ver.project_id()) {
+            Some(i) => i,
+            None => {
+                by_project.push((ver.project_id().clone(), Vec::new()));
+                by_project.len() - 1
+            }
+        };
+        let candidates = &mut by_project[idx].1;
+        match candidates.iter_mut().find(|c| c.version.id() == ver.id()) {
+            Some(c) => c.required_by.push(title),
+            None => candidates.push(VersionCandidate { version: ver, required_by: vec![title] })
+        }
+    }
+    let mut found: Vec<VersionConflict> = Vec::new();
+    for (project_id, mut candidates) in by_project {
+        let Some(selected) = mods.iter().find(|m| *m == &project_id) else { continue };
+        if candidates.iter().all(|c| c.version.id() == &selected.version_id) {
+            continue;
+        }
+        if !candidates.iter().any(|c| c.version.id() == &selected.version_id) {
+            match provider.get_version(Some(&project_id), &selected.version_id).await {
+                Ok(version) => candidates.insert(0, VersionCandidate { version, required_by: Vec::new() }),
+                Err(e) => println!("{e}")
+            }
+        }
+        found.push(VersionConflict {
+            title: selected.title.clone(),
+            project_id,
+            selected: selected.version_id.clone(),
+            candidates
+        });
+    }
+    found
+}
+
+/// Swaps each conflicting project to its newest candidate, then resolves
+/// whatever the swapped-in versions need and drops what only the replaced
+/// versions did
+async fn use_newest<P: ModProvider>(
+    provider: &P,
+    query: &VersionQuery,
+    mods: &mut Vec<Mod>,
+    optional: &arguments::OptionalDeps,
+    conflicts: &[VersionConflict],
+    graph: &mut DependencyGraph
+) {
+    let mut pending: Vec<(String, DependencyInfo)> = Vec::new();
+    for c in conflicts {
+        let Some(newest) = c.newest() else { continue };
+        println!("[CHECK/WARNING] {c}; using newest {}", newest.version_number());
+        if newest.id() == &c.selected {
+            continue;
+        }
+        let Some(idx) = mods.iter().position(|m| m == &c.project_id) else { continue };
+        match Mod::build_from_version(provider, newest.clone()).await {
+            Ok(mut m) => {
+                m.with_optional = mods[idx].with_optional;
+                pending.extend(m.queued_dependencies(optional));
+                graph.clear_children(&c.project_id);
+                mods[idx] = m;
+            },
+            Err(e) => println!("{e}")
+        }
+    }
+    work_through(provider, query, mods, optional, pending, graph).await;
+    let orphans = graph.prune_unreachable();
+    mods.retain(|m| {
+        let orphan = orphans.contains(&m.project_id);
+        if orphan {
+            println!("[{}] {} is no longer needed. Dropping...", provider.tag(), m.title);
+        }
+        !orphan
+    });
+}
+
+/// `declared_by` lists `conflicts_with` as incompatible
+pub struct Incompatibility {
+    declared_by: String,
+    declared_by_version: String,
+    conflicts_with: String,
+    conflicts_with_version: String,
+}
+
+impl Incompatibility {
+    pub fn declared_by(&self) -> &String {
+        &self.declared_by
+    }
+    pub fn conflicts_with(&self) -> &String {
+        &self.conflicts_with
+    }
+}
+
+impl fmt::Display for Incompatibility {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({}) is incompatible with {} ({})",
+            self.declared_by,
+            self.declared_by_version,
+            self.conflicts_with,
+            self.conflicts_with_version
+        )
+    }
+}
+
+/// Every incompatible edge between two mods of the set. An edge naming a
+/// version only conflicts with that exact version; one naming neither
+/// points at nothing and is skipped.
+pub fn find_incompatibilities(mods: &[Mod]) -> Vec<Incompatibility> {
+    let mut found: Vec<Incompatibility> = Vec::new();
+    for m in mods {
+        let incompatible = m.dependencies.iter().filter(|d| {
+            d.kind() == DependencyKind::Incompatible
+                && (d.project_id().is_some() || d.version_id().is_some())
+        });
+        for dep in incompatible {
+            for other in mods {
+                let project_match = dep.project_id().as_ref().is_none_or(|p| *p == other.project_id);
+                let version_match = dep.version_id().as_ref().is_none_or(|v| *v == other.version_id);
+                if other.project_id != m.project_id && project_match && version_match {
+                    found.push(Incompatibility {
+                        declared_by: m.title.clone(),
+                        declared_by_version: m.version_name.clone(),
+                        conflicts_with: other.title.clone(),
+                        conflicts_with_version: other.version_name.clone()
+                    });
+                }
+            }
+        }
+    }
+    found
+}
+
+/// Prints every incompatibility in `mods`; fails on any unless the config
+/// allows them
+pub fn check_compatibility(
+    conf: &arguments::Config<'_>,
+    mods: &[Mod]
+) -> Result<(), ModError> {
+    let found = find_incompatibilities(mods);
+    if found.is_empty() {
+        return Ok(());
+    }
+    let allowed = conf.options().get_allow_incompatible();
+    for i in &found {
+        if allowed {
+            println!("[CHECK/WARNING] {i}");
+        } else {
+            println!("[CHECK/ERROR] {i}");
+        }
+    }
+    if allowed {
+        Ok(())
+    } else {
+        Err(ModError::Incompatible(
+            format!("{} conflict(s); use --allow-incompatible to install anyway", found.len())
+        ))
+    }
+}
+
+/// Fails on version conflicts left in `resolution`, then checks the set
+/// for incompatibilities
+pub fn check_resolution(
+    conf: &arguments::Config<'_>,
+    resolution: &Resolution
+) -> Result<(), ModError> {
+    let conflicts = resolution.conflicts();
+    if !conflicts.is_empty() {
+        for c in conflicts {
+            println!("[CHECK/ERROR] {c}");
+        }
+        return Err(ModError::VersionConflict(
+            format!("{} project(s); use --prefer-newest to install the newest pinned version", conflicts.len())
+        ));
+    }
+    check_compatibility(conf, resolution.mods())
+}
+
+pub enum FileVerification {
+    Ok,
+    NotExists,
+    BadHash,
+    BadFile
+}
+
+pub async fn collect_mods<P: ModProvider>(
+    provider: &P,
+    ids: &[String],
+    query: &VersionQuery
+) -> Vec<Mod>
+{
+    let results: Vec<Result<Mod, ModError>> = stream::iter(ids)
+        .map(|id| Mod::build_from_project_id(provider, id.to_string(), query))
+        .buffered(MAX_CONCURRENT_REQUESTS)
+        .collect()
+        .await;
+    ids.iter()
+        .zip(results)
+        .filter_map(|(id, m)| m.map_err(|e| report_skipped(provider.tag(), id, &e)).ok())
+        .collect()
+}
+
+pub async fn collect_entries<P: ModProvider>(
+    provider: &P,
+    entries: &[ManifestEntry],
+    query: &VersionQuery
+) -> Vec<Mod>
+{
+    build_entries(provider, entries, query).await.0
+}
+
+/// `collect_entries`, also returning the IDs of the entries that failed
+async fn build_entries<P: ModProvider>(
+    provider: &P,
+    entries: &[ManifestEntry],
+    query: &VersionQuery
+) -> (Vec<Mod>, Vec<String>)
+{
+    let results: Vec<Result<Mod, ModError>> = stream::iter(entries)
+        .map(|e| Mod::build_from_entry(provider, e, query))
+        .buffered(MAX_CONCURRENT_REQUESTS)
+        .collect()
+        .await;
+    let mut mods: Vec<Mod> = Vec::new();
+    let mut unresolved: Vec<String> = Vec::new();
+    for (entry, m) in entries.iter().zip(results) {
+        match m {
+            Ok(m) => mods.push(m),
+            Err(e) => {
+                report_skipped(provider.tag(), entry.id(), &e);
+                unresolved.push(entry.id().clone());
+            }
+        }
+    }
+    (mods, unresolved)
+}
+
+/// Prints why `id` was left out and, for failed requests, what to do about it
+fn report_skipped(tag: &str, id: &str, e: &ModError) {
+    println!("{e}");
+    match e.hint() {
+        Some(hint) => println!("[{}] Skipped '{}': {}", tag, id, hint),
+        None => println!("[{}] Skipped '{}'", tag, id)
+    }
+}
+
+/// Downloads every mod into a staging folder next to `out_dir`, retrying
+/// each up to `retries` times. Only when all of them are downloaded and
+/// verified does the staged set replace `out_dir`, without the jars of
+/// projects it replaced; otherwise `out_dir` is left untouched and this
+/// fails.
+pub async fn download_mods(
+    client: &reqwest::Client,
+    mods: &[Mod],
+    out_dir: &Path,
+    retries: u32
+) -> Result<(), DownloadError> {
+    let mut staging = Staging::build(out_dir)?;
+    let mut download_tasks = Vec::new();
+    for m in mods {
+        download_tasks.push(m.download_with_retries(client, staging.path(), retries));
+    }
+    let results = future::join_all(download_tasks).await;
+    let mut failed: Vec<&String> = Vec::new();
+    for (m, res) in mods.iter().zip(results) {
+        if let Err(e) = res {
+            println!("{e}");
+            failed.push(m.filename());
+        }
+    };
+    if !failed.is_empty() {
+        println!("\n{} out of {} mods failed to download:", failed.len(), mods.len());
+        for filename in &failed {
+            println!("  {}", filename);
+        }
+        println!("'{}' was left unchanged", out_dir.display());
+        return Err(DownloadError::Incomplete(failed.len(), mods.len()));
+    }
+    for m in mods {
+        for old in staging.replace_project(m.project_id(), m.filename())? {
+            println!("[DOWNLOAD] Removing {}, replaced by {}", old, m.filename());
+        }
+    }
+    staging.commit()?;
+    println!("All mods downloaded successfully");
+    Ok(())
+}
+
+pub fn verify_mods(
+    mods: &[Mod],
+    out_dir: &Path
+) {
+    let mut bad_results: u32 = 0;
+    for m in mods {
+        let v_res = m.verify(out_dir);
+        if !v_res.is_ok() {
+            bad_results += 1;
+        };
+        v_res.print();
+    };
+    if bad_results > 0 {
+        println!("\n{} out of {} mods were unable to be verified", bad_results, mods.len());
+    } else {
+        println!("All mods verified successfully");
+    };
+}
+
+/// Locks an installed resolution. A set with entries that failed to
+/// resolve isn't what the manifest asks for, so it isn't locked.
+pub fn write_lockfile(
+    conf: &arguments::Config<'_>,
+    resolution: &Resolution
+) {
+    if !resolution.unresolved().is_empty() {
+        println!(
+            "[LOCK/WARNING] Not writing lockfile '{}': could not resolve {}",
+            lockfile::LOCKFILE_NAME,
+            resolution.unresolved().join(", ")
+        );
+        return;
+    }
+    let lock = Lockfile::build(
+        conf.mcvs().clone(),
+        conf.loader_as_string(),
+        resolution.mods().iter().map(Mod::lock_entry).collect()
+    );
+    match lock.write(Path::new(lockfile::LOCKFILE_NAME)) {
+        Ok(()) => println!("[LOCK] Wrote lockfile '{}'", lockfile::LOCKFILE_NAME),
+        Err(e) => println!("{e}")
+    }
+}
+
+/// Rebuilds the locked set, failing on the first entry that can't be
+/// installed exactly as pinned
+pub fn mods_from_lockfile(lock: &Lockfile) -> Result<Vec<Mod>, ModError> {
+    lock.mods()
+    .iter()
+    .map(Mod::from_lock_entry)
+    .collect()
+}
+
+async fn get_dependencies_for<P: ModProvider>(
+    conf: &arguments::Config<'_>,
+    provider: &P,
+    query: &VersionQuery,
+    mods: &mut Vec<Mod>,
+    version_conflicts: &mut Vec<VersionConflict>
+) -> DependencyGraph {
+    if conf.options().get_verify() {
+        println!("[{}] Checking provided IDs only; dependencies NOT included...", provider.tag());
+    } else if conf.options().get_skip_deps() {
+        println!("[{}] Skipping dependencies...", provider.tag());
+    } else {
+        println!("[{}] Getting dependencies...", provider.tag());
+        let mut graph = resolve_dependencies_with(provider, query, mods, conf.optional()).await;
+        let conflicts = find_version_conflicts(provider, query, mods, conf.optional()).await;
+        if conf.options().get_prefer_newest() {
+            use_newest(provider, query, mods, conf.optional(), &conflicts, &mut graph).await;
+        } else {
+            version_conflicts.extend(conflicts);
+        }
+        for (m, dep) in optional_dependencies(provider, mods).await {
+            println!(
+                "[{}] Optional for {}: {} ({}); add with --optional {} or optional=true",
+                provider.tag(),
+                m,
+                dep.get_title(),
+                dep.get_id(),
+                dep.get_id()
+            );
+        }
+        return graph;
+    }
+    roots_graph(mods)
+}
+
+/// Graph with `mods` as unconnected roots
+fn roots_graph(mods: &[Mod]) -> DependencyGraph {
+    let mut graph = DependencyGraph::new();
+    for m in mods.iter() {
+        graph.add_root(&m.project_id, &m.title);
+    }
+    graph
+}
+
+/// Optional dependencies of `mods` that aren't part of the set, paired
+/// with the title of the mod that suggests them
+pub async fn optional_dependencies<P: ModProvider>(
+    provider: &P,
+    mods: &[Mod]
+) -> Vec<(String, ProjectInfo)>
+{
+    let suggested: Vec<(&Mod, &DependencyInfo)> = mods.iter()
+        .flat_map(|m| m.dependencies.iter()
+            .filter(|d| d.kind() == DependencyKind::Optional)
+            .map(move |d| (m, d))
+        )
+        .collect();
+    let projects: Vec<Result<ProjectInfo, ModError>> = stream::iter(&suggested)
+        .map(|(_, d)| async move {
+            let project_id = match (d.project_id(), d.version_id()) {
+                (Some(p), _) => p.clone(),
+                (None, Some(v)) => provider.get_version(None, v).await?.project_id().clone(),
+                _ => return Err(ModError::NoDependency("Could not resolve dependency".to_string()))
+            };
+            provider.resolve_project(&project_id).await
+        })
+        .buffered(MAX_CONCURRENT_REQUESTS)
+        .collect()
+        .await;
+    let mut out: Vec<(String, ProjectInfo)> = Vec::new();
+    for ((m, _), proj) in suggested.into_iter().zip(projects) {
+        if let Ok(p) = proj
+        && !mods.iter().any(|x| x == p.get_id()) {
+            out.push((m.title.clone(), p));
+        }
+    }
+    out
+}
+
+/// A resolved mod set and the graph of who pulled in what
+#[derive(Default)]
+pub struct Resolution {
+    mods: Vec<Mod>,
+    graph: DependencyGraph,
+    conflicts: Vec<VersionConflict>,
+    unresolved: Vec<String>,
+}
+
+impl Resolution {
+    pub fn mods(&self) -> &Vec<Mod> {
+        &self.mods
+    }
+    pub fn graph(&self) -> &DependencyGraph {
+        &self.graph
+    }
+    /// Pins left unresolved because no conflict policy was given
+    pub fn conflicts(&self) -> &Vec<VersionConflict> {
+        &self.conflicts
+    }
+    /// Manifest entries left out because they couldn't be resolved
+    pub fn unresolved(&self) -> &Vec<String> {
+        &self.unresolved
+    }
+    /// Adds another provider's part of the set
+    pub fn merge(&mut self, other: Resolution) {
+        self.mods.extend(other.mods);
+        self.graph.merge(other.graph);
+        self.conflicts.extend(other.conflicts);
+        self.unresolved.extend(other.unresolved);
+    }
+}
+
+/// Resolves every manifest entry for the configured side through
+/// `provider`, plus dependencies unless the config skips them or only
+/// asks for verification.
+pub async fn resolve_list<P: ModProvider>(
+    conf: &arguments::Config<'_>,
+    provider: &P,
+    entries: &[ManifestEntry]
+) -> Resolution
+{
+    let query = VersionQuery::build_query(
+        conf.mcvs(),
+        &conf.loader_as_string()
+    );
+    let entries: Vec<ManifestEntry> = entries.iter()
+        .filter(|e| {
+            let wanted = e.wanted_on(conf.side());
+            if !wanted {
+                println!("[{}] Skipping '{}' (not used on this side)", provider.tag(), e.id());
+            }
+            wanted
+        })
+        .cloned()
+        .collect();
+    let cached = CachedProvider::new(provider);
+    let ids: Vec<String> = entries.iter().map(|e| e.id().clone()).collect();
+    if let Err(e) = cached.resolve_projects(&ids).await {
+        println!("{e}");
+    }
+    let (mut mods, unresolved) = build_entries(&cached, &entries, &query).await;
+    let mut conflicts: Vec<VersionConflict> = Vec::new();
+    let graph = get_dependencies_for(conf, &cached, &query, &mut mods, &mut conflicts).await;
+    print_stats(conf, &cached);
+    Resolution { mods, graph, conflicts, unresolved }
+}
+
+pub async fn resolve_single<P: ModProvider>(
+    conf: &arguments::Config<'_>,
+    provider: &P,
+    entry: &ManifestEntry
+) -> Result<Resolution, ModError>
+{
+    let query = VersionQuery::build_query(
+        conf.mcvs(),
+        &conf.loader_as_string()
+    );
+    let cached = CachedProvider::new(provider);
+    let mut mods: Vec<Mod> = vec![Mod::build_from_entry(&cached, entry, &query).await?];
+    let mut conflicts: Vec<VersionConflict> = Vec::new();
+    let graph = get_dependencies_for(conf, &cached, &query, &mut mods, &mut conflicts).await;
+    print_stats(conf, &cached);
+    Ok(Resolution { mods, graph, conflicts, unresolved: Vec::new() })
+}
+
+/// Resolves one version by its global ID, plus dependencies
+pub async fn resolve_version<P: ModProvider>(
+    conf: &arguments::Config<'_>,
+    provider: &P,
+    version_id: &str
+) -> Result<Resolution, ModError>
+{
+    let query = VersionQuery::build_query(
+        conf.mcvs(),
+        &conf.loader_as_string()
+    );
+    let cached = CachedProvider::new(provider);
+    let mut mods: Vec<Mod> = vec![
+        Mod::build_from_version_id(&cached, None, version_id.to_string()).await?
+    ];
+    let mut conflicts: Vec<VersionConflict> = Vec::new();
+    let graph = get_dependencies_for(conf, &cached, &query, &mut mods, &mut conflicts).await;
+    print_stats(conf, &cached);
+    Ok(Resolution { mods, graph, conflicts, unresolved: Vec::new() })
+}
+
+/// Prints how many requests a resolution made when `--stats` is set
+fn print_stats<P: ModProvider>(conf: &arguments::Config<'_>, cached: &CachedProvider<'_, P>) {
+    if conf.options().get_stats() {
+        println!("[{}/STATS] {}", cached.tag(), cached.stats().summary());
+    }
+}
+
+/// Verifies `mods` against `out_dir` in checkmods mode, downloads them otherwise
+pub async fn install_mods(
+    conf: &arguments::Config<'_>,
+    client: &reqwest::Client,
+    mods: &[Mod],
+    out_dir: &Path
+) -> Result<(), DownloadError> {
+    if conf.options().get_verify() {
+        println!("Checking mods against folder '{}'...", out_dir.display());
+        verify_mods(mods, out_dir);
+        Ok(())
+    } else {
+        download_mods(client, mods, out_dir, conf.options().get_retries()).await
+    }
+}
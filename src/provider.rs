@@ -0,0 +1,442 @@
+use std::future::Future;
+use std::{fmt, fs, error, io};
+use std::io::Read;
+use std::path::Path;
+use futures::future;
+use serde::Serialize;
+use sha1::Sha1;
+use sha2::{Sha512, Digest};
+
+/// Bytes read at a time when hashing a file
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Debug)]
+pub enum ModError {
+    NoFileForProj(String),
+    BadRequest(reqwest::Error),
+    NoVersionForId(String),
+    NoDependency(String),
+    BadLockEntry(String),
+    BadId(String),
+    NoApiKey(String),
+    Incompatible(String),
+    VersionConflict(String),
+    NotFound(String, String),
+    Unauthorized(String, String),
+    RateLimited(String, String),
+    ServerError(u16, String, String),
+    BadStatus(u16, String, String),
+}
+
+impl fmt::Display for ModError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoFileForProj(proj_title) => write!(f, "[PROVIDER/ERROR] No file for project: {}", proj_title),
+            Self::BadRequest(err) => write!(f, "[PROVIDER/ERROR] Bad request: {}", err),
+            Self::NoVersionForId(id) => write!(f, "[PROVIDER/ERROR] No version for ID: {}", id),
+            Self::NoDependency(msg) => write!(f, "[PROVIDER/ERROR] No dependency: {}", msg),
+            Self::BadLockEntry(msg) => write!(f, "[PROVIDER/ERROR] Bad lockfile entry: {}", msg),
+            Self::BadId(id) => write!(f, "[PROVIDER/ERROR] Invalid ID: {}", id),
+            Self::NoApiKey(msg) => write!(f, "[PROVIDER/ERROR] No API key: {}", msg),
+            Self::Incompatible(msg) => write!(f, "[PROVIDER/ERROR] Incompatible mods: {}", msg),
+            Self::VersionConflict(msg) => write!(f, "[PROVIDER/ERROR] Conflicting versions: {}", msg),
+            Self::NotFound(id, url) => write!(f, "[PROVIDER/ERROR] Not found: '{}' ({})", id, url),
+            Self::Unauthorized(id, url) => write!(f, "[PROVIDER/ERROR] Not authorized for '{}' ({})", id, url),
+            Self::RateLimited(id, url) => write!(f, "[PROVIDER/ERROR] Still rate limited after retrying for '{}' ({})", id, url),
+            Self::ServerError(status, id, url) => write!(f, "[PROVIDER/ERROR] Server error {} for '{}' ({})", status, id, url),
+            Self::BadStatus(status, id, url) => write!(f, "[PROVIDER/ERROR] HTTP {} for '{}' ({})", status, id, url),
+        }
+    }
+}
+
+impl error::Error for ModError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::BadRequest(err) => Some(err),
+            _ => None
+        }
+    }
+}
+
+impl ModError {
+    /// What the user can do about a failed request, when there's something
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            Self::NotFound(..) => Some("Check the ID or slug; the project or version doesn't exist"),
+            Self::Unauthorized(..) => Some("Check the API key, or whether the project is private"),
+            Self::RateLimited(..) => Some("Too many requests; wait a minute and run again"),
+            Self::ServerError(..) => Some("The API is having trouble; try again later"),
+            _ => None
+        }
+    }
+}
+
+impl From<reqwest::Error> for ModError {
+    fn from(value: reqwest::Error) -> Self {
+        Self::BadRequest(value)
+    }
+}
+
+/// A source of mods. Implementors translate their API into the shared
+/// info types so the download/verify pipeline never sees provider details.
+pub trait ModProvider {
+    /// Prefix for log lines, e.g. `MODRINTH`
+    fn tag(&self) -> &'static str;
+    fn resolve_project(
+        &self,
+        id: &str
+    ) -> impl Future<Output = Result<ProjectInfo, ModError>>;
+    /// Versions matching `query`, newest first
+    fn list_versions(
+        &self,
+        project_id: &str,
+        query: &VersionQuery
+    ) -> impl Future<Output = Result<Vec<VersionInfo>, ModError>>;
+    fn get_version(
+        &self,
+        project_id: Option<&str>,
+        version_id: &str
+    ) -> impl Future<Output = Result<VersionInfo, ModError>>;
+    fn resolve_dependency(
+        &self,
+        dep: &DependencyInfo,
+        query: &VersionQuery
+    ) -> impl Future<Output = Result<VersionInfo, ModError>>;
+    /// Several projects at once, for providers with a bulk endpoint. IDs
+    /// that don't resolve are left out.
+    fn resolve_projects(
+        &self,
+        ids: &[String]
+    ) -> impl Future<Output = Result<Vec<ProjectInfo>, ModError>> {
+        async move {
+            Ok(future::join_all(ids.iter().map(|id| self.resolve_project(id)))
+                .await
+                .into_iter()
+                .filter_map(Result::ok)
+                .collect()
+            )
+        }
+    }
+    /// Several versions at once by version ID; unknown IDs are left out
+    fn get_versions(
+        &self,
+        version_ids: &[String]
+    ) -> impl Future<Output = Result<Vec<VersionInfo>, ModError>> {
+        async move {
+            Ok(future::join_all(version_ids.iter().map(|id| self.get_version(None, id)))
+                .await
+                .into_iter()
+                .filter_map(Result::ok)
+                .collect()
+            )
+        }
+    }
+    /// How many requests `resolve_projects` or `get_versions` makes for
+    /// `count` IDs; one each, unless the provider has a bulk endpoint
+    fn bulk_requests(&self, count: usize) -> usize {
+        count
+    }
+    /// The file to install for `version`, with its hashes
+    fn file_info(&self, version: &VersionInfo) -> Option<FileInfo>;
+}
+
+#[derive(Clone)]
+pub struct ProjectInfo {
+    id: String,
+    title: String,
+}
+
+impl ProjectInfo {
+    pub fn new(id: String, title: String) -> Self {
+        ProjectInfo { id, title }
+    }
+    pub fn get_id(&self) -> &String {
+        &self.id
+    }
+    pub fn get_title(&self) -> &String {
+        &self.title
+    }
+}
+
+#[derive(Clone)]
+pub struct VersionInfo {
+    id: String,
+    project_id: String,
+    name: String,
+    version_number: String,
+    version_type: Option<String>,
+    published: Option<String>,
+    files: Vec<FileInfo>,
+    dependencies: Vec<DependencyInfo>,
+}
+
+impl VersionInfo {
+    pub fn new(
+        id: String,
+        project_id: String,
+        name: String,
+        version_number: String,
+        version_type: Option<String>,
+        files: Vec<FileInfo>,
+        dependencies: Vec<DependencyInfo>
+    ) -> Self {
+        VersionInfo {
+            id,
+            project_id,
+            name,
+            version_number,
+            version_type,
+            published: None,
+            files,
+            dependencies
+        }
+    }
+    /// Sets the RFC 3339 publish time used to tell which version is newer
+    pub fn with_published(mut self, published: String) -> Self {
+        self.published = Some(published);
+        self
+    }
+    pub fn id(&self) -> &String {
+        &self.id
+    }
+    pub fn project_id(&self) -> &String {
+        &self.project_id
+    }
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+    pub fn version_number(&self) -> &String {
+        &self.version_number
+    }
+    /// `release`, `beta` or `alpha`, when the provider reports it
+    pub fn version_type(&self) -> &Option<String> {
+        &self.version_type
+    }
+    pub fn published(&self) -> &Option<String> {
+        &self.published
+    }
+    pub fn files(&self) -> &Vec<FileInfo> {
+        &self.files
+    }
+    pub fn dependencies(&self) -> &Vec<DependencyInfo> {
+        &self.dependencies
+    }
+}
+
+#[derive(Clone)]
+pub struct FileInfo {
+    url: String,
+    filename: String,
+    primary: bool,
+    hashes: FileHashes,
+    size: Option<u64>,
+}
+
+impl FileInfo {
+    pub fn new(
+        url: String,
+        filename: String,
+        primary: bool,
+        hashes: FileHashes,
+        size: Option<u64>
+    ) -> Self {
+        FileInfo { url, filename, primary, hashes, size }
+    }
+    pub fn url(&self) -> &String {
+        &self.url
+    }
+    pub fn filename(&self) -> &String {
+        &self.filename
+    }
+    pub fn primary(&self) -> bool {
+        self.primary
+    }
+    pub fn hashes(&self) -> &FileHashes {
+        &self.hashes
+    }
+    pub fn size(&self) -> Option<u64> {
+        self.size
+    }
+}
+
+#[derive(Clone)]
+pub struct FileHashes {
+    sha512: Option<Vec<u8>>,
+    sha1: Option<Vec<u8>>,
+}
+
+impl FileHashes {
+    pub fn new(sha512: Option<Vec<u8>>, sha1: Option<Vec<u8>>) -> Self {
+        FileHashes { sha512, sha1 }
+    }
+    pub fn sha512(&self) -> &Option<Vec<u8>> {
+        &self.sha512
+    }
+    pub fn sha1(&self) -> &Option<Vec<u8>> {
+        &self.sha1
+    }
+    /// Checks against the strongest hash available; a file with no
+    /// known hash never passes.
+    pub fn check(&self, bytes: &[u8]) -> bool {
+        let mut digester = Digester::new();
+        digester.update(bytes);
+        self.matches(&digester.finish())
+    }
+    /// `check` for data already hashed by a `Digester`
+    pub fn matches(&self, digest: &FileDigest) -> bool {
+        if let Some(sha512) = &self.sha512 {
+            *sha512 == digest.sha512
+        } else if let Some(sha1) = &self.sha1 {
+            *sha1 == digest.sha1
+        } else {
+            false
+        }
+    }
+    /// `check` for a file on disk, read in chunks
+    pub fn check_file(&self, path: &Path) -> io::Result<bool> {
+        Ok(self.matches(&FileDigest::of_file(path)?))
+    }
+}
+
+/// Hashes data chunk by chunk as it streams past, so no file has to be
+/// held in memory whole
+#[derive(Default)]
+pub struct Digester {
+    sha1: Sha1,
+    sha512: Sha512,
+    size: u64,
+}
+
+impl Digester {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn update(&mut self, chunk: &[u8]) {
+        self.sha1.update(chunk);
+        self.sha512.update(chunk);
+        self.size += chunk.len() as u64;
+    }
+    pub fn finish(self) -> FileDigest {
+        FileDigest {
+            sha1: self.sha1.finalize().to_vec(),
+            sha512: self.sha512.finalize().to_vec(),
+            size: self.size
+        }
+    }
+}
+
+/// Both hashes and the length of some data
+pub struct FileDigest {
+    sha1: Vec<u8>,
+    sha512: Vec<u8>,
+    size: u64,
+}
+
+impl FileDigest {
+    pub fn of_file(path: &Path) -> io::Result<Self> {
+        let mut file = fs::File::open(path)?;
+        let mut digester = Digester::new();
+        let mut buf = vec![0u8; READ_CHUNK_SIZE];
+        loop {
+            let n = file.read(&mut buf)?;
+            if n == 0 {
+                return Ok(digester.finish());
+            }
+            digester.update(&buf[..n]);
+        }
+    }
+    pub fn sha1(&self) -> &Vec<u8> {
+        &self.sha1
+    }
+    pub fn sha512(&self) -> &Vec<u8> {
+        &self.sha512
+    }
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DependencyKind {
+    Required,
+    Optional,
+    Incompatible,
+    /// Shipped inside the mod's own jar, so never installed separately
+    Embedded,
+}
+
+#[derive(Clone)]
+pub struct DependencyInfo {
+    version_id: Option<String>,
+    project_id: Option<String>,
+    kind: DependencyKind,
+}
+
+impl DependencyInfo {
+    pub fn new(
+        version_id: Option<String>,
+        project_id: Option<String>,
+        kind: DependencyKind
+    ) -> Self {
+        DependencyInfo { version_id, project_id, kind }
+    }
+    pub fn kind(&self) -> DependencyKind {
+        self.kind
+    }
+    pub fn version_id(&self) -> &Option<String> {
+        &self.version_id
+    }
+    pub fn project_id(&self) -> &Option<String> {
+        &self.project_id
+    }
+}
+
+#[derive(Serialize)]
+pub struct VersionQuery {
+    game_versions: String,
+    loaders: String,
+    #[serde(skip)]
+    game_version_list: Vec<String>,
+    #[serde(skip)]
+    loader_list: Vec<String>,
+}
+
+impl VersionQuery {
+    fn build_param_array(user_params: &str) -> String {
+        let mut params = user_params.split(",");
+        let mut res: String = String::from("[");
+        res = format!("{}\"{}\"",
+            res,
+            params.next().unwrap_or(""),
+        );
+        for prm in params {
+            res = format!("{},\"{}\"",
+                res,
+                prm,
+            );
+        }
+        format!("{}]", res)
+    }
+    pub fn build_query(user_mcvs: &str, user_loader: &str) -> VersionQuery {
+        let game_versions= Self::build_param_array(user_mcvs);
+        let loaders= Self::build_param_array(user_loader);
+        VersionQuery {
+            game_versions,
+            loaders,
+            game_version_list: user_mcvs.split(",").map(String::from).collect(),
+            loader_list: user_loader.split(",").map(String::from).collect(),
+        }
+    }
+    pub fn mcvs(&self) -> &str {
+        self.game_versions.as_str()
+    }
+    pub fn loader(&self) -> &str {
+        self.loaders.as_str()
+    }
+    pub fn mcvs_list(&self) -> &Vec<String> {
+        &self.game_version_list
+    }
+    pub fn loader_list(&self) -> &Vec<String> {
+        &self.loader_list
+    }
+}
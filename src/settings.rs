@@ -0,0 +1,82 @@
+use std::fs;
+use std::path::Path;
+use std::{fmt, io, error};
+use serde::Deserialize;
+
+pub const SETTINGS_FILE_NAME: &str = "mcmodgetter.toml";
+pub const MODRINTH_URL_VAR: &str = "MCMODGETTER_MODRINTH_URL";
+
+#[derive(Debug)]
+pub enum SettingsError {
+    BadFile(io::Error),
+    BadFormat(toml::de::Error),
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BadFile(err) => write!(f, "[SETTINGS/ERROR] Bad config file: {}", err),
+            Self::BadFormat(err) => write!(f, "[SETTINGS/ERROR] Could not parse config file: {}", err),
+        }
+    }
+}
+
+impl error::Error for SettingsError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::BadFile(err) => Some(err),
+            Self::BadFormat(err) => Some(err),
+        }
+    }
+}
+
+impl From<io::Error> for SettingsError {
+    fn from(value: io::Error) -> Self {
+        Self::BadFile(value)
+    }
+}
+
+impl From<toml::de::Error> for SettingsError {
+    fn from(value: toml::de::Error) -> Self {
+        Self::BadFormat(value)
+    }
+}
+
+/// Values read from the config file; CLI flags and env vars take priority.
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Settings {
+    modrinth_url: Option<String>,
+    curseforge_url: Option<String>,
+    curseforge_api_key: Option<String>,
+}
+
+impl Settings {
+    pub fn read(path: &Path) -> Result<Self, SettingsError> {
+        let contents = fs::read_to_string(path)?;
+        Ok(toml::from_str(&contents)?)
+    }
+    /// Reads `path` if given, otherwise the default file when one exists
+    pub fn load(path: Option<&Path>) -> Result<Self, SettingsError> {
+        match path {
+            Some(p) => Self::read(p),
+            None => {
+                let default_path = Path::new(SETTINGS_FILE_NAME);
+                if default_path.exists() {
+                    Self::read(default_path)
+                } else {
+                    Ok(Self::default())
+                }
+            }
+        }
+    }
+    pub fn modrinth_url(&self) -> &Option<String> {
+        &self.modrinth_url
+    }
+    pub fn curseforge_url(&self) -> &Option<String> {
+        &self.curseforge_url
+    }
+    pub fn curseforge_api_key(&self) -> &Option<String> {
+        &self.curseforge_api_key
+    }
+}
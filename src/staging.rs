@@ -0,0 +1,233 @@
+use std::collections::BTreeMap;
+use std::{fmt, fs, io, error, process};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Which project each jar in a folder was installed for, so the next
+/// install can drop the jars of projects it replaces
+pub const RECORD_NAME: &str = ".mcmodgetter-installed.json";
+
+#[derive(Debug)]
+pub enum StagingError {
+    BadFile(io::Error),
+    NoPrevious(PathBuf),
+}
+
+impl fmt::Display for StagingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BadFile(err) => write!(f, "[STAGING/ERROR] Bad file: {}", err),
+            Self::NoPrevious(path) => write!(f, "[STAGING/ERROR] Nothing to roll back to: '{}' does not exist", path.display()),
+        }
+    }
+}
+
+impl error::Error for StagingError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::BadFile(err) => Some(err),
+            _ => None
+        }
+    }
+}
+
+impl From<io::Error> for StagingError {
+    fn from(value: io::Error) -> Self {
+        Self::BadFile(value)
+    }
+}
+
+/// `<out>.previous`, where the set replaced by the last install is kept
+pub fn previous_dir(out_dir: &Path) -> PathBuf {
+    sibling(out_dir, "", ".previous")
+}
+
+fn sibling(out_dir: &Path, prefix: &str, suffix: &str) -> PathBuf {
+    let name = out_dir.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    out_dir.with_file_name(format!("{prefix}{name}{suffix}"))
+}
+
+/// A hidden sibling named for this process and moment, so concurrent
+/// runs never share one
+fn unique_sibling(out_dir: &Path, label: &str) -> PathBuf {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    sibling(out_dir, ".", &format!(".{}-{}-{}", label, process::id(), nanos))
+}
+
+/// A copy of the output folder, built next to it, that an install is
+/// written into. The output folder only changes when the finished set is
+/// swapped in. Unchanged files are hard links, so staging costs little
+/// space. Dropping a `Staging` without committing it deletes it.
+pub struct Staging {
+    out_dir: PathBuf,
+    dir: PathBuf,
+    record: BTreeMap<String, String>,
+    record_changed: bool,
+}
+
+impl Staging {
+    pub fn build(out_dir: &Path) -> io::Result<Self> {
+        fs::create_dir_all(out_dir)?;
+        let out_dir = fs::canonicalize(out_dir)?;
+        let dir = unique_sibling(&out_dir, "staging");
+        let staging = Staging {
+            record: read_record(&out_dir),
+            record_changed: false,
+            out_dir,
+            dir
+        };
+        fs::create_dir_all(&staging.dir)?;
+        link_tree(&staging.out_dir, &staging.dir)?;
+        Ok(staging)
+    }
+    pub fn path(&self) -> &PathBuf {
+        &self.dir
+    }
+    /// Records `filename` as `project_id`'s jar and drops any other jar
+    /// recorded for that project from the staged set. Returns the names
+    /// of the dropped jars.
+    pub fn replace_project(&mut self, project_id: &str, filename: &str) -> io::Result<Vec<String>> {
+        let superseded: Vec<String> = self.record.iter()
+            .filter(|(f, p)| *p == project_id && *f != filename)
+            .map(|(f, _)| f.clone())
+            .collect();
+        for old in &superseded {
+            self.record.remove(old);
+            match fs::remove_file(self.dir.join(old)) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => ()
+            }
+        }
+        if self.record.get(filename).map(String::as_str) != Some(project_id) {
+            self.record.insert(filename.to_string(), project_id.to_string());
+            self.record_changed = true;
+        }
+        self.record_changed |= !superseded.is_empty();
+        Ok(superseded)
+    }
+    /// Swaps the staged set in if it differs from the output folder, which
+    /// then becomes `<out>.previous`. Returns whether anything changed;
+    /// when nothing did, `<out>.previous` is left alone.
+    pub fn commit(self) -> io::Result<bool> {
+        if self.record_changed {
+            write_record(&self.dir, &self.record)?;
+        }
+        if !differs(&self.out_dir, &self.dir)? {
+            println!("[STAGING] Nothing changed in '{}'", self.out_dir.display());
+            return Ok(false);
+        }
+        let previous = previous_dir(&self.out_dir);
+        let retired = unique_sibling(&self.out_dir, "retired");
+        if previous.exists() {
+            fs::rename(&previous, &retired)?;
+        }
+        fs::rename(&self.out_dir, &previous)?;
+        if let Err(e) = fs::rename(&self.dir, &self.out_dir) {
+            fs::rename(&previous, &self.out_dir)?;
+            if retired.exists() {
+                fs::rename(&retired, &previous)?;
+            }
+            return Err(e);
+        }
+        if retired.exists() {
+            fs::remove_dir_all(&retired)?;
+        }
+        println!("[STAGING] Installed into '{}', previous set kept in '{}'",
+            self.out_dir.display(),
+            previous.display()
+        );
+        Ok(true)
+    }
+}
+
+impl Drop for Staging {
+    fn drop(&mut self) {
+        // Already gone once committed
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Mirrors `from` into `to` with hard links, copying where a link can't be made
+fn link_tree(from: &Path, to: &Path) -> io::Result<()> {
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            fs::create_dir_all(&target)?;
+            link_tree(&entry.path(), &target)?;
+        } else if fs::hard_link(entry.path(), &target).is_err() {
+            fs::copy(entry.path(), &target)?;
+            // Keep the timestamp so the copy doesn't count as a change
+            let modified = entry.metadata()?.modified()?;
+            fs::File::options().write(true).open(&target)?.set_modified(modified)?;
+        }
+    }
+    Ok(())
+}
+
+/// Whether the two trees hold different files. Files still linked to the
+/// original share its size and timestamp; anything written since doesn't.
+fn differs(a: &Path, b: &Path) -> io::Result<bool> {
+    let mut names: Vec<_> = fs::read_dir(a)?
+        .map(|e| e.map(|e| e.file_name()))
+        .collect::<io::Result<_>>()?;
+    let b_names: Vec<_> = fs::read_dir(b)?
+        .map(|e| e.map(|e| e.file_name()))
+        .collect::<io::Result<_>>()?;
+    if names.len() != b_names.len() {
+        return Ok(true);
+    }
+    names.sort();
+    for name in names {
+        let (a_path, b_path) = (a.join(&name), b.join(&name));
+        let (Ok(a_meta), Ok(b_meta)) = (fs::metadata(&a_path), fs::metadata(&b_path)) else {
+            return Ok(true);
+        };
+        let changed = if a_meta.is_dir() && b_meta.is_dir() {
+            differs(&a_path, &b_path)?
+        } else {
+            a_meta.is_dir() != b_meta.is_dir()
+                || a_meta.len() != b_meta.len()
+                || a_meta.modified()? != b_meta.modified()?
+        };
+        if changed {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+fn read_record(dir: &Path) -> BTreeMap<String, String> {
+    fs::read(dir.join(RECORD_NAME))
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .unwrap_or_default()
+}
+
+fn write_record(dir: &Path, record: &BTreeMap<String, String>) -> io::Result<()> {
+    let path = dir.join(RECORD_NAME);
+    // Replace rather than rewrite, since the staged copy is a link to the original
+    let _ = fs::remove_file(&path);
+    fs::write(path, serde_json::to_vec_pretty(record)?)
+}
+
+/// Puts `<out>.previous` back in place. The set it replaces becomes the
+/// new `<out>.previous`, so rolling back twice undoes the rollback.
+pub fn rollback(out_dir: &Path) -> Result<(), StagingError> {
+    let out_dir = fs::canonicalize(out_dir)?;
+    let previous = previous_dir(&out_dir);
+    if !previous.is_dir() {
+        return Err(StagingError::NoPrevious(previous));
+    }
+    let swap = unique_sibling(&out_dir, "rollback");
+    fs::rename(&out_dir, &swap)?;
+    fs::rename(&previous, &out_dir)?;
+    fs::rename(&swap, &previous)?;
+    println!("[STAGING] Restored '{}' from '{}'", out_dir.display(), previous.display());
+    Ok(())
+}
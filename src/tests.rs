@@ -1,31 +1,233 @@
 use super::*;
 use modrinth::*;
+use pipeline::*;
+use provider::*;
+use sha1::Digest;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// Serves canned responses keyed by request path (query string ignored)
+/// so provider tests can run without a live API. Any `MOCK_URL` in a body
+/// is replaced with the server's own address.
+async fn mock_server(routes: Vec<(String, u16, Vec<u8>)>) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.expect("Should bind");
+    let addr = listener.local_addr().expect("Should have address");
+    let base_url = format!("http://{addr}");
+    let routes: Vec<(String, u16, Vec<u8>)> = routes.into_iter()
+        .map(|(path, status, body)| {
+            let body = match String::from_utf8(body) {
+                Ok(text) => text.replace("MOCK_URL", &base_url).into_bytes(),
+                Err(e) => e.into_bytes()
+            };
+            (path, status, body)
+        })
+        .collect();
+    tokio::spawn(async move {
+        while let Ok((mut sock, _)) = listener.accept().await {
+            let mut buf: Vec<u8> = Vec::new();
+            let mut chunk = [0u8; 1024];
+            while !buf.windows(4).any(|w| w == b"\r\n\r\n") {
+                match sock.read(&mut chunk).await {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => buf.extend_from_slice(&chunk[..n])
+                }
+            }
+            // Drain any request body so the client isn't reset mid-response
+            let header_end = buf.windows(4).position(|w| w == b"\r\n\r\n").map_or(buf.len(), |p| p + 4);
+            let head = String::from_utf8_lossy(&buf[..header_end]).to_string();
+            let body_len = head.lines()
+                .find_map(|l| l.to_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse::<usize>().unwrap_or(0)))
+                .unwrap_or(0);
+            while buf.len() < header_end + body_len {
+                match sock.read(&mut chunk).await {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => buf.extend_from_slice(&chunk[..n])
+                }
+            }
+            let target = head.split(' ').nth(1).unwrap_or("");
+            let (path, query) = target.split_once('?').unwrap_or((target, ""));
+            let (status, body) = bulk_response(&routes, path, query)
+                .or(routes.iter()
+                    .find(|(p, _, _)| p == path)
+                    .map(|(_, status, body)| (*status, body.clone()))
+                )
+                .unwrap_or((404, b"{}".to_vec()));
+            let header = format!(
+                "HTTP/1.1 {} MOCK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                status,
+                body.len()
+            );
+            let _ = sock.write_all(header.as_bytes()).await;
+            let _ = sock.write_all(&body).await;
+        }
+    });
+    base_url
+}
+
+/// Answers `/v2/projects?ids=[...]` and `/v2/versions?ids=[...]` from the
+/// single-item routes, leaving out IDs without one
+fn bulk_response(routes: &[(String, u16, Vec<u8>)], path: &str, query: &str) -> Option<(u16, Vec<u8>)> {
+    let prefix = match path {
+        "/v2/projects" => "/v2/project/",
+        "/v2/versions" => "/v2/version/",
+        _ => return None
+    };
+    let encoded = query.split('&').find_map(|p| p.strip_prefix("ids="))?;
+    let mut decoded: Vec<u8> = Vec::new();
+    let mut bytes = encoded.bytes();
+    while let Some(b) = bytes.next() {
+        match b {
+            b'%' => {
+                let hex: Vec<u8> = bytes.by_ref().take(2).collect();
+                decoded.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+            },
+            b'+' => decoded.push(b' '),
+            _ => decoded.push(b)
+        }
+    }
+    let ids: Vec<String> = serde_json::from_slice(&decoded).ok()?;
+    let bodies: Vec<String> = ids.iter()
+        .filter_map(|id| routes.iter().find(|(p, status, _)| *status == 200 && *p == format!("{prefix}{id}")))
+        .map(|(_, _, body)| String::from_utf8_lossy(body).to_string())
+        .collect();
+    Some((200, format!("[{}]", bodies.join(",")).into_bytes()))
+}
+
+/// Stand-in Modrinth API serving the recorded responses in `fixtures/modrinth`,
+/// plus a fake jar under `/cdn/<filename>` for every file they reference.
+async fn modrinth_fixtures() -> Modrinth {
+    modrinth_fixtures_with(Vec::new()).await
+}
+
+/// Fixture API with extra routes, for endpoints whose response depends on
+/// the test (e.g. hash lookups)
+async fn modrinth_fixtures_with(extra: Vec<(String, u16, Vec<u8>)>) -> Modrinth {
+    let fixture_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/modrinth");
+    let mut routes: Vec<(String, u16, Vec<u8>)> = Vec::new();
+    for entry in fs::read_dir(fixture_dir).expect("Fixtures should exist") {
+        let path = entry.expect("Should read entry").path();
+        let name = path.file_stem().expect("Should have name").to_string_lossy().to_string();
+        let body = fs::read(&path).expect("Should read fixture");
+        let route = match name.split_once('_') {
+            Some(("project", id)) => format!("/v2/project/{id}"),
+            Some(("versions", id)) => format!("/v2/project/{id}/version"),
+            Some(("version", id)) => format!("/v2/version/{id}"),
+            _ => continue
+        };
+        let json: serde_json::Value = serde_json::from_slice(&body).expect("Fixture should be JSON");
+        let versions = match json.as_array() {
+            Some(list) => list.clone(),
+            None => vec![json]
+        };
+        if let Some(("versions", id)) = name.split_once('_') {
+            for v in &versions {
+                let v_body = serde_json::to_vec(v).expect("Should serialize");
+                for key in [&v["id"], &v["version_number"]] {
+                    let key = key.as_str().unwrap_or("");
+                    routes.push((format!("/v2/project/{id}/version/{key}"), 200, v_body.clone()));
+                }
+            }
+        }
+        for file in versions.iter().filter_map(|v| v["files"].as_array()).flatten() {
+            let filename = file["filename"].as_str().unwrap_or("");
+            routes.push((format!("/cdn/{filename}"), 200, format!("jar:{filename}").into_bytes()));
+        }
+        routes.push((route, 200, body));
+    }
+    // Extra routes come first so a test can override a fixture
+    routes.splice(0..0, extra);
+    let client = create_client().expect("Client should be created");
+    Modrinth::build(&client, &mock_server(routes).await)
+}
+
+/// Status, extra headers and body of one scripted response
+type ScriptedResponse = (u16, Vec<(&'static str, &'static str)>, String);
+
+/// Answers every request with the next of `responses`, repeating the last
+/// one, and counts the requests it got
+async fn scripted_server(
+    responses: Vec<ScriptedResponse>
+) -> (String, std::sync::Arc<std::sync::atomic::AtomicUsize>) {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.expect("Should bind");
+    let base_url = format!("http://{}", listener.local_addr().expect("Should have address"));
+    let hits = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let counter = hits.clone();
+    tokio::spawn(async move {
+        while let Ok((mut sock, _)) = listener.accept().await {
+            let mut buf: Vec<u8> = Vec::new();
+            let mut chunk = [0u8; 1024];
+            while !buf.windows(4).any(|w| w == b"\r\n\r\n") {
+                match sock.read(&mut chunk).await {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => buf.extend_from_slice(&chunk[..n])
+                }
+            }
+            let n = counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            let (status, headers, body) = &responses[n.min(responses.len() - 1)];
+            let extra: String = headers.iter().map(|(k, v)| format!("{k}: {v}\r\n")).collect();
+            let header = format!(
+                "HTTP/1.1 {} MOCK\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n",
+                status,
+                body.len(),
+                extra
+            );
+            let _ = sock.write_all(header.as_bytes()).await;
+            let _ = sock.write_all(body.as_bytes()).await;
+        }
+    });
+    (base_url, hits)
+}
+
+fn temp_out_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("mcmodgetter_{name}"));
+    let _ = fs::remove_dir_all(&dir);
+    let _ = fs::remove_dir_all(staging::previous_dir(&dir));
+    fs::create_dir_all(&dir).expect("Should create dir");
+    dir
+}
 
 #[tokio::test]
 async fn get_project_sodium() {
-    let client = create_client().expect("Client should be created");
-    let project = get_project(&client, "AANobbMI").await.expect("should exist");
+    let modrinth = modrinth_fixtures().await;
+    let project = get_project(&modrinth, "AANobbMI").await.expect("should exist");
     assert_eq!(project.get_title(), "Sodium");
     assert_eq!(project.get_id(), "AANobbMI")
 }
 
 #[tokio::test]
 async fn get_list_of_projects() {
-    let client = create_client().expect("Client should be created");
-    let ids_vec = vec![String::from("P7dR8mSH"), String::from("AANobbMI"), String::from("9s6osm5g")];
-    let project_vec = get_projects_from_list(&client, &ids_vec).await;
-    assert_eq!(project_vec[0].as_ref().expect("Should exist").get_title(), "Fabric API");
-    assert_eq!(project_vec[1].as_ref().expect("Should exist").get_title(), "Sodium")
+    let modrinth = modrinth_fixtures().await;
+    let ids_vec = vec![String::from("P7dR8mSH"), String::from("notAProj"), String::from("AANobbMI"), String::from("9s6osm5g")];
+    let project_vec = get_projects_from_list(&modrinth, &ids_vec).await.expect("Should load");
+    assert_eq!(project_vec.len(), 3);
+    assert_eq!(project_vec[0].get_title(), "Fabric API");
+    assert_eq!(project_vec[1].get_title(), "Sodium")
+}
+
+#[tokio::test]
+async fn get_list_of_versions_in_chunks() {
+    let modrinth = modrinth_fixtures().await;
+    // Enough filler IDs to need a second chunk
+    let mut ids: Vec<String> = (0..150).map(|i| format!("missing{i}")).collect();
+    ids.insert(0, String::from("KhCFoeip"));
+    ids.push(String::from("u1OBWrvJ"));
+    let versions = get_versions_from_list(&modrinth, &ids).await.expect("Should load");
+    let found: Vec<&String> = versions.iter().map(|v| v.id()).collect();
+    assert_eq!(found, vec!["KhCFoeip", "u1OBWrvJ"]);
+
+    // Each chunk counts as a request
+    let cached = cache::CachedProvider::new(&modrinth);
+    assert_eq!(cached.get_versions(&ids).await.expect("Should load").len(), 2);
+    assert_eq!(cached.stats().versions().requests(), 2);
 }
 
 #[tokio::test]
 async fn get_version_sodium() {
-    let client = create_client().expect("Client should be created");
+    let modrinth = modrinth_fixtures().await;
     let query = VersionQuery::build_query(
         &String::from("1.21.8"), 
         &String::from("fabric")
     );
-    let versions = get_version(&client, "AANobbMI", &query).await.expect("should exist");
+    let versions = get_version(&modrinth, "AANobbMI", &query).await.expect("should exist");
     let v = &versions[0];
     assert_eq!(v.id(), "7pwil2dy");
     assert_eq!(v.name(), "Sodium 0.7.3 for Fabric 1.21.8");
@@ -36,12 +238,12 @@ async fn get_version_sodium() {
 
 #[tokio::test]
 async fn get_top_version_sodium() {
-    let client = create_client().expect("Client should be created");
+    let modrinth = modrinth_fixtures().await;
     let query = VersionQuery::build_query(
         &String::from("1.21.8"), 
         &String::from("fabric")
     );
-    let v = get_top_version(&client, "AANobbMI", &query).await.expect("Should work");
+    let v = get_top_version(&modrinth, "AANobbMI", &query).await.expect("Should work");
     assert_eq!(v.id(), "7pwil2dy");
     assert_eq!(v.name(), "Sodium 0.7.3 for Fabric 1.21.8");
     assert_eq!(v.version_number(), "mc1.21.8-0.7.3-fabric");
@@ -51,12 +253,12 @@ async fn get_top_version_sodium() {
 
 #[tokio::test]
 async fn get_top_version_capes_with_dependencies() {
-    let client = create_client().expect("Client should be created");
+    let modrinth = modrinth_fixtures().await;
     let query = VersionQuery::build_query(
         &String::from("1.21.8"), 
         &String::from("fabric")
     );
-    let v = get_top_version(&client, "89Wsn8GD", &query).await.expect("Should work");
+    let v = get_top_version(&modrinth, "89Wsn8GD", &query).await.expect("Should work");
     assert_eq!(v.id(), "GRuX8d2G");
     assert_eq!(v.name(), "[Fabric 1.21.6-8] Capes 1.5.9");
     let _p_id1 = String::from("P7dR8mSH");
@@ -67,13 +269,13 @@ async fn get_top_version_capes_with_dependencies() {
 
 #[tokio::test]
 async fn get_primary_file_for_latest_sodium() {
-    let client = create_client().expect("Client should be created");
+    let modrinth = modrinth_fixtures().await;
     let query = VersionQuery::build_query(
         &String::from("1.21.9,1.21.10"), 
         &String::from("fabric")
     );
-    let v = get_top_version(&client, "AANobbMI", &query).await.expect("should exist");
-    let file_index = search_for_primary_file(&v.files()).expect("Should be Some");
+    let v = get_top_version(&modrinth, "AANobbMI", &query).await.expect("should exist");
+    let file_index = search_for_primary_file(v.files()).expect("Should be Some");
     assert_eq!(file_index, 0);
     assert!(v.files()[0].primary());
 }
@@ -87,7 +289,7 @@ fn build_modrinth_query() {
 
 #[test]
 fn build_modrinth_query_from_empty() {
-    let query = VersionQuery::build_query(&String::from(""), &String::from(""));
+    let query = VersionQuery::build_query("", "");
     assert_eq!(query.mcvs(), "[\"\"]");
     assert_eq!(query.loader(), "[\"\"]");
 }
@@ -104,23 +306,102 @@ fn parse_line_from_mmg_file() {
     let curse_line = String::from("349239 -curse");
     let modrinth_line1 = String::from("SRlzjEBS -modrinth");
     let modrinth_line2 = String::from("SRlzjEBS");
-    let curse_parse = file_parse::parse_input_line(&curse_line).expect("should be some");
-    let modrinth_parse1 = file_parse::parse_input_line(&modrinth_line1).expect("should be some");
-    let modrinth_parse2 = file_parse::parse_input_line(&modrinth_line2).expect("should be some");
-    assert!(matches!(curse_parse, file_parse::IdType::Curseforge("349239")));
-    assert!(matches!(modrinth_parse1, file_parse::IdType::Modrinth("SRlzjEBS")));
-    assert!(matches!(modrinth_parse2, file_parse::IdType::Modrinth("SRlzjEBS")));
+    let curse_parse = file_parse::parse_input_line(&curse_line).expect("should parse").expect("should be some");
+    let modrinth_parse1 = file_parse::parse_input_line(&modrinth_line1).expect("should parse").expect("should be some");
+    let modrinth_parse2 = file_parse::parse_input_line(&modrinth_line2).expect("should parse").expect("should be some");
+    assert_eq!(curse_parse.source(), file_parse::Source::Curseforge);
+    assert_eq!(curse_parse.id(), "349239");
+    assert_eq!(modrinth_parse1.source(), file_parse::Source::Modrinth);
+    assert_eq!(modrinth_parse1.id(), "SRlzjEBS");
+    assert_eq!(modrinth_parse2.source(), file_parse::Source::Modrinth);
+    assert_eq!(modrinth_parse2.id(), "SRlzjEBS");
+
+    assert!(file_parse::parse_input_line("").expect("should parse").is_none());
+}
+
+#[test]
+fn parse_ids_with_comments_and_diagnostics() {
+    assert!(file_parse::parse_input_line("   ").expect("should parse").is_none());
+    assert!(file_parse::parse_input_line("# Sodium").expect("should parse").is_none());
+    let noted = file_parse::parse_input_line("AANobbMI   # rendering, keep below 0.8")
+        .expect("should parse")
+        .expect("should be some");
+    assert_eq!(noted.id(), "AANobbMI");
+    let curse = file_parse::parse_input_line("349239\t-curse# shaders")
+        .expect("should parse")
+        .expect("should be some");
+    assert_eq!(curse.source(), file_parse::Source::Curseforge);
+
+    let path = std::env::temp_dir().join("mcmodgetter_commented_ids.txt");
+    fs::write(&path, "# Performance\nAANobbMI # Sodium\n\nP7dR8mSH\n").expect("Should write");
+    let ids = file_parse::parse_ids(&path).expect("Should parse");
+    assert_eq!(ids.modrinth().as_ref().expect("Should have ids").len(), 2);
 
-    let empty = String::new();
-    let empty_parse = file_parse::parse_input_line(&empty).expect("should be some");
-    assert!(matches!(empty_parse, file_parse::IdType::Modrinth("")));
+    fs::write(&path, "AANobbMI bogus\n# fine\nP7dR8mSH side=both\n").expect("Should write");
+    let err = file_parse::parse_ids(&path).err().expect("Should fail");
+    fs::remove_file(&path).expect("Should remove");
+    let file_parse::ManifestError::BadLines(diagnostics) = err else { panic!("Expected bad lines") };
+    assert_eq!(diagnostics.iter().map(|d| d.line()).collect::<Vec<usize>>(), vec![1, 3]);
+    assert_eq!(diagnostics[0].text(), "AANobbMI bogus");
+}
+
+#[test]
+fn parse_manifest_constraints() {
+    let entry = file_parse::parse_input_line(
+        "AANobbMI version_number=mc1.21.8-*-fabric types=release,beta loader=quilt side=client"
+    ).expect("should parse").expect("should be some");
+    assert_eq!(entry.version_number().as_deref(), Some("mc1.21.8-*-fabric"));
+    assert_eq!(entry.version_types().as_ref().expect("should have types"), &vec!["release", "beta"]);
+    assert_eq!(entry.loader().as_deref(), Some("quilt"));
+    assert!(entry.wanted_on(&arguments::Side::Client));
+    assert!(!entry.wanted_on(&arguments::Side::Server));
+    let query = VersionQuery::build_query("1.21.8", "fabric");
+    assert_eq!(entry.query(&query).loader(), r#"["quilt"]"#);
+
+    let pinned = file_parse::parse_input_line("238222 source=curseforge version=5000")
+        .expect("should parse")
+        .expect("should be some");
+    assert_eq!(pinned.source(), file_parse::Source::Curseforge);
+    assert_eq!(pinned.version_id().as_deref(), Some("5000"));
+
+    assert!(file_parse::parse_input_line("SRlzjEBS -fabric").is_err());
+    assert!(file_parse::parse_input_line("SRlzjEBS side=both").is_err());
+    assert!(file_parse::parse_input_line("SRlzjEBS -curse source=modrinth").is_err());
+
+    let path = std::env::temp_dir().join("mcmodgetter_bad_manifest.txt");
+    fs::write(&path, "AANobbMI\nP7dR8mSH typo\n").expect("Should write");
+    let err = file_parse::parse_ids(&path).err().expect("Should fail");
+    fs::remove_file(&path).expect("Should remove");
+    assert!(matches!(err, file_parse::ManifestError::BadLines(d) if d[0].line() == 2));
+}
+
+#[tokio::test]
+async fn build_mod_from_constrained_entry() {
+    let modrinth = modrinth_fixtures().await;
+    let query = VersionQuery::build_query("1.21.8", "fabric");
+    let older = file_parse::parse_input_line("AANobbMI version_number=*-0.7.2-*")
+        .expect("should parse")
+        .expect("should be some");
+    let m = Mod::build_from_entry(&modrinth, &older, &query).await.expect("Should resolve");
+    assert_eq!(m.version_id(), "u1OBWrvJ");
+
+    let beta_only = file_parse::parse_input_line("AANobbMI types=beta")
+        .expect("should parse")
+        .expect("should be some");
+    assert!(Mod::build_from_entry(&modrinth, &beta_only, &query).await.is_err());
+
+    let pinned = file_parse::parse_input_line("AANobbMI version=u1OBWrvJ")
+        .expect("should parse")
+        .expect("should be some");
+    let m = Mod::build_from_entry(&modrinth, &pinned, &query).await.expect("Should resolve");
+    assert_eq!(m.version_id(), "u1OBWrvJ");
 }
 
 #[tokio::test]
 async fn build_mod() {
-    let client = create_client().expect("Client should be created");
+    let modrinth = modrinth_fixtures().await;
     let query = VersionQuery::build_query(&String::from("1.21.8"), &String::from("fabric"));
-    let modrinth_mod = Mod::build_from_project_id(&client, String::from("uXXizFIs"), &query).await.expect("Should no errors");
+    let modrinth_mod = Mod::build_from_project_id(&modrinth, String::from("uXXizFIs"), &query).await.expect("Should no errors");
     assert_eq!(modrinth_mod.title(), "FerriteCore");
     assert_eq!(modrinth_mod.filename(), "ferritecore-8.0.0-fabric.jar");
     assert_eq!(modrinth_mod.version_name(), "ferritecore-8.0.0-fabric");
@@ -128,13 +409,1052 @@ async fn build_mod() {
 
 #[tokio::test]
 async fn get_dependencies() {
-    let client = create_client().expect("client should work");
+    let modrinth = modrinth_fixtures().await;
     let query = VersionQuery::build_query(&String::from("1.21.8"), &String::from("fabric"));
     let mut mods: Vec<Mod> = Vec::new();
-    mods.push(Mod::build_from_project_id(&client, String::from("89Wsn8GD"), &query).await.expect("Should be no errors")); //capes +2
-    mods.push(Mod::build_from_project_id(&client, String::from("mOgUt4GM"), &query).await.expect("Should be no errors")); //modmenu +1  
-    mods.push(Mod::build_from_project_id(&client, String::from("DFqQfIBR"), &query).await.expect("Should be no errors")); //craftpresence +1
-    mods.push(Mod::build_from_project_id(&client, String::from("UMxybHE8"), &query).await.expect("Should be no errors")); //minihud +1
-    resolve_dependencies(&client, &query, &mut mods).await;
+    mods.push(Mod::build_from_project_id(&modrinth, String::from("89Wsn8GD"), &query).await.expect("Should be no errors")); //capes +2
+    mods.push(Mod::build_from_project_id(&modrinth, String::from("mOgUt4GM"), &query).await.expect("Should be no errors")); //modmenu +1  
+    mods.push(Mod::build_from_project_id(&modrinth, String::from("DFqQfIBR"), &query).await.expect("Should be no errors")); //craftpresence +1
+    mods.push(Mod::build_from_project_id(&modrinth, String::from("UMxybHE8"), &query).await.expect("Should be no errors")); //minihud +1
+    resolve_dependencies(&modrinth, &query, &mut mods).await;
     assert_eq!(mods.len(), 9);
-}
\ No newline at end of file
+}
+
+#[test]
+fn lockfile_round_trip() {
+    let entry = lockfile::LockedMod::new(
+        String::from("Sodium"),
+        String::from("AANobbMI"),
+        String::from("7pwil2dy"),
+        String::from("Sodium 0.7.3 for Fabric 1.21.8"),
+        String::from("sodium-fabric-0.7.3+mc1.21.8.jar"),
+        String::from("https://cdn.modrinth.com/data/AANobbMI/versions/7pwil2dy/sodium-fabric-0.7.3+mc1.21.8.jar"),
+        lockfile::LockedHashes::new(Some(String::from("ab12cd34")), None)
+    );
+    let m = Mod::from_lock_entry(&entry).expect("Should be valid entry");
+    assert_eq!(m.version_id(), "7pwil2dy");
+    assert_eq!(m.filename(), "sodium-fabric-0.7.3+mc1.21.8.jar");
+
+    let lock = lockfile::Lockfile::build(
+        String::from("1.21.8"),
+        String::from("fabric"),
+        vec![m.lock_entry()]
+    );
+    let path = std::env::temp_dir().join("mcmodgetter_lockfile_round_trip.lock");
+    lock.write(&path).expect("Should write");
+    let read = lockfile::Lockfile::read(&path).expect("Should read");
+    fs::remove_file(&path).expect("Should remove");
+    assert_eq!(read.game_versions(), "1.21.8");
+    assert_eq!(read.mods()[0].project_id(), "AANobbMI");
+    assert_eq!(read.mods()[0].sha512().as_deref(), Some("ab12cd34"));
+}
+
+#[test]
+fn lockfile_entry_with_bad_hash() {
+    let entry = lockfile::LockedMod::new(
+        String::from("Sodium"),
+        String::from("AANobbMI"),
+        String::from("7pwil2dy"),
+        String::from("Sodium 0.7.3 for Fabric 1.21.8"),
+        String::from("sodium-fabric-0.7.3+mc1.21.8.jar"),
+        String::new(),
+        lockfile::LockedHashes::new(Some(String::from("not hex")), None)
+    );
+    assert!(matches!(Mod::from_lock_entry(&entry), Err(ModError::BadLockEntry(_))));
+
+    // One bad entry fails the whole locked set, naming the entry
+    let good = Mod::from_lock_entry(&lockfile::LockedMod::new(
+        String::from("Fabric API"),
+        String::from("P7dR8mSH"),
+        String::from("KhCFoeip"),
+        String::from("0.133.4+1.21.8"),
+        String::from("fabric-api-0.133.4+1.21.8.jar"),
+        String::new(),
+        lockfile::LockedHashes::new(Some(String::from("ab12cd34")), None)
+    )).expect("Should be valid entry");
+    let lock = lockfile::Lockfile::build(String::from("1.21.8"), String::from("fabric"), vec![good.lock_entry(), entry]);
+    match mods_from_lockfile(&lock) {
+        Err(ModError::BadLockEntry(msg)) => assert!(msg.contains("sodium-fabric-0.7.3+mc1.21.8.jar")),
+        _ => panic!("Bad entry should fail the set")
+    }
+}
+
+fn curse_file_json(file_id: u32, mod_id: u32, jar: &[u8], deps: &str) -> String {
+    format!(
+        r#"{{"id":{file_id},"modId":{mod_id},"displayName":"file {file_id}","fileName":"mod-{mod_id}.jar","fileDate":"2025-0{file_id}-01T00:00:00Z","downloadUrl":"MOCK_URL/dl/{mod_id}.jar","hashes":[{{"value":"{}","algo":1}}],"dependencies":[{deps}]}}"#,
+        hex::encode(sha1::Sha1::digest(jar))
+    )
+}
+
+#[tokio::test]
+async fn curseforge_files_are_paged_and_deduplicated() {
+    let client = create_client().expect("Client should be created");
+    let page = |files: &[u32], total: usize| format!(
+        r#"{{"data":[{}],"pagination":{{"index":0,"pageSize":50,"resultCount":{},"totalCount":{total}}}}}"#,
+        files.iter().map(|id| curse_file_json(*id, 100, b"jar", "")).collect::<Vec<String>>().join(","),
+        files.len()
+    );
+    let (url, hits) = scripted_server(vec![
+        (200, vec![], page(&[3, 2], 3)),
+        (200, vec![], page(&[1], 3)),
+        (200, vec![], page(&[2, 1], 2)),
+    ]).await;
+    let cf = curseforge::CurseForge::build(&client, &url, "test-key");
+    let query = VersionQuery::build_query("1.21.8,1.21.7", "fabric");
+
+    let files = cf.get_files(100, &query).await.expect("Should list files");
+    let ids: Vec<u32> = files.iter().map(|f| f.id()).collect();
+    assert_eq!(ids, vec![3, 2, 1]);
+    assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn curseforge_resolve_and_download_with_deps() {
+    let client = create_client().expect("Client should be created");
+    let main_jar = b"main mod jar".to_vec();
+    let dep_jar = b"dependency jar".to_vec();
+    let main_files = format!(
+        "[{},{}]",
+        curse_file_json(1, 100, b"old", ""),
+        curse_file_json(2, 100, &main_jar, r#"{"modId":200,"relationType":3},{"modId":300,"relationType":2}"#)
+    );
+    let dep_files = format!("[{}]", curse_file_json(3, 200, &dep_jar, ""));
+    let routes = vec![
+        (String::from("/v1/mods/100"), 200, br#"{"data":{"id":100,"name":"Main Mod"}}"#.to_vec()),
+        (String::from("/v1/mods/200"), 200, br#"{"data":{"id":200,"name":"Dep Mod"}}"#.to_vec()),
+        (String::from("/v1/mods/100/files"), 200, format!(r#"{{"data":{main_files}}}"#).into_bytes()),
+        (String::from("/v1/mods/200/files"), 200, format!(r#"{{"data":{dep_files}}}"#).into_bytes()),
+        (String::from("/dl/100.jar"), 200, main_jar),
+        (String::from("/dl/200.jar"), 200, dep_jar),
+    ];
+    let base_url = mock_server(routes).await;
+    let cf = curseforge::CurseForge::build(&client, &base_url, "test-key");
+    let query = VersionQuery::build_query("1.21.8", "fabric");
+
+    let mut mods = collect_mods(&cf, &[String::from("100")], &query).await;
+    assert_eq!(mods.len(), 1);
+    assert_eq!(mods[0].version_id(), "2");
+    resolve_dependencies(&cf, &query, &mut mods).await;
+    assert_eq!(mods.len(), 2);
+    assert_eq!(mods[1].title(), "Dep Mod");
+
+    let out_dir = temp_out_dir("curseforge_download");
+    download_mods(&client, &mods, &out_dir, 0).await.expect("Should download");
+    assert!(matches!(mods[0].verify_against(&out_dir.join("mod-100.jar")), FileVerification::Ok));
+    assert!(matches!(mods[1].verify_against(&out_dir.join("mod-200.jar")), FileVerification::Ok));
+    fs::remove_dir_all(&out_dir).expect("Should remove");
+}
+
+#[tokio::test]
+async fn download_from_modrinth_fixtures() {
+    let modrinth = modrinth_fixtures().await;
+    let query = VersionQuery::build_query("1.21.8", "fabric");
+    let mut mods = collect_mods(&modrinth, &[String::from("UMxybHE8")], &query).await;
+    resolve_dependencies(&modrinth, &query, &mut mods).await;
+    assert_eq!(mods.len(), 2);
+
+    let out_dir = temp_out_dir("modrinth_download");
+    let client = create_client().expect("Client should be created");
+    download_mods(&client, &mods, &out_dir, 0).await.expect("Should download");
+    for m in &mods {
+        assert!(matches!(m.verify_against(&out_dir.join(m.filename())), FileVerification::Ok));
+    }
+    fs::remove_dir_all(&out_dir).expect("Should remove");
+}
+
+#[tokio::test]
+async fn download_verified_only_keeps_matching_files() {
+    let jar = vec![7u8; 200 * 1024];
+    let base_url = mock_server(vec![
+        ("/cdn/big.jar".to_string(), 200, jar.clone())
+    ]).await;
+    let url = format!("{base_url}/cdn/big.jar");
+    let out_dir = temp_out_dir("download_verified");
+    let client = create_client().expect("Client should be created");
+
+    let good = FileHashes::new(Some(sha2::Sha512::digest(&jar).to_vec()), None);
+    let path = out_dir.join("big.jar");
+    assert!(download_verified(&client, &url, &good, &path).await.expect("Should download"));
+    assert_eq!(fs::read(&path).expect("Should exist"), jar);
+    let digest = FileDigest::of_file(&path).expect("Should hash");
+    assert_eq!(digest.size(), jar.len() as u64);
+    assert!(good.matches(&digest));
+
+    let bad = FileHashes::new(Some(vec![0u8; 64]), None);
+    let path = out_dir.join("bad.jar");
+    assert!(!download_verified(&client, &url, &bad, &path).await.expect("Should download"));
+    assert!(!path.exists());
+    let leftovers: Vec<_> = fs::read_dir(&out_dir).expect("Should list")
+        .map(|e| e.expect("Should read").file_name())
+        .collect();
+    assert_eq!(leftovers, vec![std::ffi::OsString::from("big.jar")]);
+    fs::remove_dir_all(&out_dir).expect("Should remove");
+}
+
+fn locked_mod_at(url: &str, jar: &[u8]) -> Mod {
+    locked_mod_named(url, "retry-mod.jar", jar)
+}
+
+fn locked_mod_named(url: &str, filename: &str, jar: &[u8]) -> Mod {
+    let entry = lockfile::LockedMod::new(
+        String::from("Retry Mod"),
+        String::from("retryMod"),
+        String::from("retryV1"),
+        String::from("1.0.0"),
+        String::from(filename),
+        format!("{url}/cdn/{filename}"),
+        lockfile::LockedHashes::new(Some(hex::encode(sha2::Sha512::digest(jar))), None)
+    );
+    Mod::from_lock_entry(&entry).expect("Should be valid entry")
+}
+
+/// Staging and rollback folders left next to `out_dir`
+fn staging_leftovers(out_dir: &Path) -> usize {
+    let name = out_dir.file_name().expect("Should have name").to_string_lossy().to_string();
+    fs::read_dir(out_dir.parent().expect("Should have parent"))
+        .expect("Should list")
+        .filter(|e| e.as_ref().is_ok_and(|e| {
+            let n = e.file_name().to_string_lossy().to_string();
+            n.starts_with(&format!(".{name}.staging-")) || n.starts_with(&format!(".{name}.rollback-"))
+        }))
+        .count()
+}
+
+#[tokio::test]
+async fn download_mods_retries_and_fails_on_hash_mismatch() {
+    let args: Vec<String> = vec!["mcmodgetter", "-id", "AANobbMI", "-mcv", "1.21.8", "--retries", "5"]
+        .into_iter()
+        .map(String::from)
+        .collect();
+    let conf = arguments::Config::build_from_args(&args).expect("Should parse");
+    assert_eq!(conf.options().get_retries(), 5);
+    let args: Vec<String> = vec!["mcmodgetter", "-id", "AANobbMI", "-mcv", "1.21.8", "--retries", "many"]
+        .into_iter()
+        .map(String::from)
+        .collect();
+    assert!(arguments::Config::build_from_args(&args).is_err());
+
+    let client = create_client().expect("Client should be created");
+    let (url, hits) = scripted_server(vec![
+        (200, vec![], String::from("tampered")),
+        (200, vec![], String::from("jar:retry")),
+    ]).await;
+    let mods = vec![locked_mod_at(&url, b"jar:retry")];
+    let out_dir = temp_out_dir("download_retry");
+    download_mods(&client, &mods, &out_dir, 1).await.expect("Second attempt should pass");
+    assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 2);
+    assert!(matches!(mods[0].verify_against(&out_dir.join("retry-mod.jar")), FileVerification::Ok));
+    fs::remove_dir_all(&out_dir).expect("Should remove");
+
+    let (url, hits) = scripted_server(vec![(200, vec![], String::from("tampered"))]).await;
+    let mods = vec![locked_mod_at(&url, b"jar:retry")];
+    let out_dir = temp_out_dir("download_mismatch");
+    let result = download_mods(&client, &mods, &out_dir, 2).await;
+    assert!(matches!(result, Err(DownloadError::Incomplete(1, 1))));
+    assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 3);
+    assert!(!out_dir.join("retry-mod.jar").exists());
+    fs::remove_dir_all(&out_dir).expect("Should remove");
+}
+
+#[tokio::test]
+async fn download_mods_swaps_in_whole_set_and_rolls_back() {
+    let client = create_client().expect("Client should be created");
+    let out_dir = temp_out_dir("staged_install");
+    fs::write(out_dir.join("retry-mod.jar"), "old build").expect("Should write");
+    fs::write(out_dir.join("notes.txt"), "mine").expect("Should write");
+    let previous = staging::previous_dir(&out_dir);
+
+    let (url, _) = scripted_server(vec![(200, vec![], String::from("tampered"))]).await;
+    let result = download_mods(&client, &[locked_mod_at(&url, b"jar:retry")], &out_dir, 0).await;
+    assert!(matches!(result, Err(DownloadError::Incomplete(1, 1))));
+    assert_eq!(fs::read(out_dir.join("retry-mod.jar")).expect("Should exist"), b"old build");
+    assert_eq!(staging_leftovers(&out_dir), 0);
+    assert!(!previous.exists());
+
+    let (url, hits) = scripted_server(vec![(200, vec![], String::from("jar:retry"))]).await;
+    download_mods(&client, &[locked_mod_at(&url, b"jar:retry")], &out_dir, 0).await.expect("Should install");
+    assert_eq!(fs::read(out_dir.join("retry-mod.jar")).expect("Should exist"), b"jar:retry");
+    assert_eq!(fs::read(out_dir.join("notes.txt")).expect("Should be carried over"), b"mine");
+    assert_eq!(fs::read(previous.join("retry-mod.jar")).expect("Should be kept"), b"old build");
+    assert_eq!(staging_leftovers(&out_dir), 0);
+
+    // Nothing to do leaves the last known-good set alone
+    download_mods(&client, &[locked_mod_at(&url, b"jar:retry")], &out_dir, 0).await.expect("Should install");
+    assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 1);
+    assert_eq!(fs::read(previous.join("retry-mod.jar")).expect("Should be kept"), b"old build");
+    assert_eq!(staging_leftovers(&out_dir), 0);
+
+    // A new version of the project replaces its old jar
+    let (url, _) = scripted_server(vec![(200, vec![], String::from("jar:retry2"))]).await;
+    download_mods(&client, &[locked_mod_named(&url, "retry-mod-2.jar", b"jar:retry2")], &out_dir, 0)
+        .await
+        .expect("Should install");
+    assert!(!out_dir.join("retry-mod.jar").exists());
+    assert_eq!(fs::read(out_dir.join("retry-mod-2.jar")).expect("Should exist"), b"jar:retry2");
+    assert_eq!(fs::read(out_dir.join("notes.txt")).expect("Should be carried over"), b"mine");
+    assert_eq!(fs::read(previous.join("retry-mod.jar")).expect("Should be kept"), b"jar:retry");
+
+    rollback_mods(&out_dir).expect("Should roll back");
+    assert_eq!(fs::read(out_dir.join("retry-mod.jar")).expect("Should exist"), b"jar:retry");
+    assert!(!out_dir.join("retry-mod-2.jar").exists());
+    assert!(previous.join("retry-mod-2.jar").exists());
+    assert_eq!(staging_leftovers(&out_dir), 0);
+    fs::remove_dir_all(&previous).expect("Should remove");
+    assert!(rollback_mods(&out_dir).is_err());
+    fs::remove_dir_all(&out_dir).expect("Should remove");
+}
+
+#[test]
+fn modrinth_url_from_settings_file() {
+    let path = std::env::temp_dir().join("mcmodgetter_settings.toml");
+    fs::write(&path, "modrinth_url = \"http://localhost:8000\"\n").expect("Should write");
+    let args: Vec<String> = vec!["mcmodgetter", "-id", "AANobbMI", "-mcv", "1.21.8", "--config"]
+        .into_iter()
+        .map(String::from)
+        .chain([path.display().to_string()])
+        .collect();
+    let mut conf = arguments::Config::build_from_args(&args).expect("Should parse");
+    conf.load_settings().expect("Should load");
+    fs::remove_file(&path).expect("Should remove");
+    assert_eq!(conf.settings().modrinth_url().as_deref(), Some("http://localhost:8000"));
+
+    let args: Vec<String> = vec!["mcmodgetter", "-id", "AANobbMI", "-mcv", "1.21.8", "--modrinth-url", "http://mirror/"]
+        .into_iter()
+        .map(String::from)
+        .collect();
+    let conf = arguments::Config::build_from_args(&args).expect("Should parse");
+    let modrinth = Modrinth::from_config(&conf, &create_client().expect("Client should be created"));
+    assert_eq!(modrinth.base_url(), "http://mirror");
+}
+
+#[tokio::test]
+async fn import_mrpack_with_env_and_overrides() {
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+    let base_url = mock_server(vec![
+        ("/pack/both.jar".to_string(), 200, b"jar:both".to_vec()),
+        ("/pack/server.jar".to_string(), 200, b"jar:server".to_vec()),
+    ]).await;
+    let file_json = |path: &str, jar: &[u8], url: &str, client: &str| format!(
+        r#"{{"path":"{}","hashes":{{"sha1":"{}","sha512":"{}"}},"env":{{"client":"{}","server":"required"}},"downloads":["{}"],"fileSize":{}}}"#,
+        path,
+        hex::encode(sha1::Sha1::digest(jar)),
+        hex::encode(sha2::Sha512::digest(jar)),
+        client,
+        url,
+        jar.len()
+    );
+    let index = format!(
+        r#"{{"formatVersion":1,"game":"minecraft","versionId":"1.0.0","name":"Test Pack","files":[{},{}],"dependencies":{{"minecraft":"1.21.8","fabric-loader":"0.17.2"}}}}"#,
+        file_json("mods/both.jar", b"jar:both", &format!("{base_url}/pack/both.jar"), "required"),
+        file_json("mods/server.jar", b"jar:server", &format!("{base_url}/pack/server.jar"), "unsupported")
+    );
+    let dir = temp_out_dir("mrpack");
+    let pack_path = dir.join("test.mrpack");
+    let mut zip = zip::ZipWriter::new(fs::File::create(&pack_path).expect("Should create pack"));
+    for (name, body) in [
+        (mrpack::INDEX_NAME, index.as_str()),
+        ("overrides/config/test.toml", "shared"),
+        ("overrides/options.txt", "shared"),
+        ("client-overrides/config/test.toml", "client"),
+        ("server-overrides/server.properties", "server"),
+    ] {
+        zip.start_file(name, SimpleFileOptions::default()).expect("Should start file");
+        zip.write_all(body.as_bytes()).expect("Should write file");
+    }
+    zip.finish().expect("Should finish pack");
+
+    let root = dir.join("instance");
+    let client = create_client().expect("Client should be created");
+    let mut pack = mrpack::Modpack::open(&pack_path).expect("Pack should open");
+    assert_eq!(pack.index().name(), "Test Pack");
+    mrpack::install_files(&client, pack.index(), &arguments::Side::Client, &root)
+        .await
+        .expect("Files should install");
+    assert_eq!(pack.extract_overrides(&root, &arguments::Side::Client).expect("Should extract"), 2);
+    assert_eq!(fs::read(root.join("mods/both.jar")).expect("Should exist"), b"jar:both");
+    assert!(!root.join("mods/server.jar").exists());
+    assert_eq!(fs::read_to_string(root.join("config/test.toml")).expect("Should exist"), "client");
+    assert_eq!(fs::read_to_string(root.join("options.txt")).expect("Should exist"), "shared");
+    assert!(!root.join("server.properties").exists());
+    assert!(mrpack::pack_path(&root, "../escape.jar").is_err());
+    assert!(mrpack::pack_path(&root, "/etc/escape.jar").is_err());
+
+    // Without -o there is no instance folder to stage
+    let args: Vec<String> = vec!["mcmodgetter".to_string(), "--mrpack".to_string(), pack_path.display().to_string()];
+    assert!(arguments::Config::build_from_args(&args).is_err());
+
+    // Importing through staging, then again with nothing new, keeps the first result
+    let staged_root = dir.join("staged_instance");
+    let args: Vec<String> = vec!["mcmodgetter".to_string(), "--mrpack".to_string(), pack_path.display().to_string(), "-o".to_string(), staged_root.display().to_string()];
+    let conf = arguments::Config::build_from_args(&args).expect("Should parse");
+    import_mrpack(&conf, &client, &pack_path).await.expect("Should import");
+    fs::write(staging::previous_dir(&staged_root).join("marker"), "").expect("Should write");
+    import_mrpack(&conf, &client, &pack_path).await.expect("Should import");
+    assert!(staging::previous_dir(&staged_root).join("marker").exists());
+    assert_eq!(fs::read_to_string(staged_root.join("config/test.toml")).expect("Should exist"), "client");
+    assert_eq!(fs::read(staged_root.join("mods/both.jar")).expect("Should exist"), b"jar:both");
+    fs::remove_dir_all(staging::previous_dir(&staged_root)).expect("Should remove");
+
+    // A file that fails its hash check fails the import, before any overrides
+    let bad_index = format!(
+        r#"{{"formatVersion":1,"game":"minecraft","versionId":"1.0.0","name":"Bad Pack","files":[{}],"dependencies":{{"minecraft":"1.21.8"}}}}"#,
+        file_json("mods/both.jar", b"jar:other", &format!("{base_url}/pack/both.jar"), "required")
+    );
+    let bad_path = dir.join("bad.mrpack");
+    let mut zip = zip::ZipWriter::new(fs::File::create(&bad_path).expect("Should create pack"));
+    for (name, body) in [(mrpack::INDEX_NAME, bad_index.as_str()), ("overrides/options.txt", "shared")] {
+        zip.start_file(name, SimpleFileOptions::default()).expect("Should start file");
+        zip.write_all(body.as_bytes()).expect("Should write file");
+    }
+    zip.finish().expect("Should finish pack");
+    let bad_root = dir.join("bad_instance");
+    let args: Vec<String> = vec!["mcmodgetter".to_string(), "--mrpack".to_string(), bad_path.display().to_string(), "-o".to_string(), bad_root.display().to_string()];
+    let conf = arguments::Config::build_from_args(&args).expect("Should parse");
+    assert!(import_mrpack(&conf, &client, &bad_path).await.is_err());
+    assert!(!bad_root.join("mods/both.jar").exists());
+    assert!(!bad_root.join("options.txt").exists());
+    fs::remove_dir_all(&dir).expect("Should remove");
+}
+
+#[tokio::test]
+async fn export_mrpack_round_trip() {
+    let modrinth = modrinth_fixtures().await;
+    let query = VersionQuery::build_query("1.21.8", "fabric");
+    let mut mods = collect_mods(&modrinth, &[String::from("UMxybHE8")], &query).await;
+    resolve_dependencies(&modrinth, &query, &mut mods).await;
+    let dir = temp_out_dir("mrpack_export");
+    let out_dir = dir.join("mods");
+    let config_dir = dir.join("config");
+    fs::create_dir_all(config_dir.join("minihud")).expect("Should create config");
+    fs::write(config_dir.join("minihud/minihud.json"), "{}").expect("Should write config");
+
+    let files: Vec<mrpack::PackFile> = mods.iter()
+        .map(|m| mrpack::PackFile::from_mod(m, &out_dir).expect("Fixture files have hashes"))
+        .collect();
+    let index = mrpack::PackIndex::build(
+        String::from("Exported"),
+        String::from("1.0.0"),
+        "1.21.8",
+        (mrpack::loader_dependency("fabric").expect("Should map"), "0.17.2"),
+        files
+    );
+    let pack_path = dir.join("exported.mrpack");
+    assert_eq!(index.write(&pack_path, Some(&config_dir)).expect("Should write pack"), 1);
+
+    let mut pack = mrpack::Modpack::open(&pack_path).expect("Pack should open");
+    assert_eq!(pack.index().dependencies()["minecraft"], "1.21.8");
+    assert_eq!(pack.index().dependencies()["fabric-loader"], "0.17.2");
+    assert_eq!(pack.index().files().len(), 2);
+    let root = dir.join("instance");
+    let client = create_client().expect("Client should be created");
+    mrpack::install_files(&client, pack.index(), &arguments::Side::Client, &root)
+        .await
+        .expect("Files should install");
+    pack.extract_overrides(&root, &arguments::Side::Client).expect("Should extract");
+    for m in &mods {
+        assert!(matches!(m.verify_against(&root.join("mods").join(m.filename())), FileVerification::Ok));
+    }
+    assert!(root.join("config/minihud/minihud.json").exists());
+
+    // Lockfile entries carry no size, so it comes from the installed copy
+    let locked = Mod::from_lock_entry(&mods[0].lock_entry()).expect("Should rebuild");
+    assert!(mrpack::PackFile::from_mod(&locked, &out_dir).is_err());
+    assert!(mrpack::PackFile::from_mod(&locked, &root.join("mods")).is_ok());
+
+    // Exporting a mod that can't be added fails instead of writing a partial pack
+    let args: Vec<String> = vec!["mcmodgetter", "--locked", "--export", "partial.mrpack", "--loader-version", "0.17.2"]
+        .into_iter()
+        .map(String::from)
+        .collect();
+    let conf = arguments::Config::build_from_args(&args).expect("Should parse");
+    let partial_path = dir.join("partial.mrpack");
+    assert!(export_mrpack(&conf, &[locked], &partial_path, &out_dir, "1.21.8", "fabric").is_err());
+    assert!(!partial_path.exists());
+    fs::remove_dir_all(&dir).expect("Should remove");
+}
+
+fn fixture(name: &str) -> String {
+    fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/modrinth").join(name))
+        .expect("Fixture should exist")
+}
+
+fn jar_sha512(filename: &str) -> String {
+    hex::encode(sha2::Sha512::digest(format!("jar:{filename}")))
+}
+
+#[tokio::test]
+async fn update_replaces_and_archives_old_jar() {
+    let old_jar = "sodium-fabric-0.7.2+mc1.21.8.jar";
+    let new_jar = "sodium-fabric-0.7.3+mc1.21.8.jar";
+    let hash_map = format!(r#"{{"{}":{}}}"#, jar_sha512(old_jar), fixture("version_u1OBWrvJ.json"));
+    let modrinth = modrinth_fixtures_with(vec![
+        (String::from("/v2/version_files"), 200, hash_map.into_bytes())
+    ]).await;
+    let dir = temp_out_dir("update");
+    let out_dir = dir.join("mods");
+    let archive_dir = dir.join("archive");
+    fs::create_dir_all(&out_dir).expect("Should create");
+    fs::write(out_dir.join(old_jar), format!("jar:{old_jar}")).expect("Should write");
+    fs::write(out_dir.join("handmade.jar"), "unknown").expect("Should write");
+
+    let args: Vec<String> = vec!["mcmodgetter", "update", "-mcv", "1.21.8", "--modrinth-url", modrinth.base_url()]
+        .into_iter()
+        .map(String::from)
+        .chain(["--archive".to_string(), archive_dir.display().to_string()])
+        .collect();
+    let conf = arguments::Config::build_from_args(&args).expect("Should parse");
+    let client = create_client().expect("Client should be created");
+    update_mods(&conf, &client, &out_dir).await.expect("Update should run");
+
+    assert!(out_dir.join(new_jar).exists());
+    assert!(!out_dir.join(old_jar).exists());
+    assert!(archive_dir.join(old_jar).exists());
+    assert!(out_dir.join("handmade.jar").exists());
+    assert!(staging::previous_dir(&out_dir).join(old_jar).exists());
+    fs::remove_dir_all(&dir).expect("Should remove");
+}
+
+#[tokio::test]
+async fn update_with_failures_changes_nothing() {
+    let old_jar = "sodium-fabric-0.7.2+mc1.21.8.jar";
+    let new_jar = "sodium-fabric-0.7.3+mc1.21.8.jar";
+    let hash_map = format!(r#"{{"{}":{}}}"#, jar_sha512(old_jar), fixture("version_u1OBWrvJ.json"));
+    let modrinth = modrinth_fixtures_with(vec![
+        (String::from("/v2/version_files"), 200, hash_map.into_bytes()),
+        (format!("/cdn/{new_jar}"), 200, b"tampered".to_vec())
+    ]).await;
+    let out_dir = temp_out_dir("update_failure");
+    fs::write(out_dir.join(old_jar), format!("jar:{old_jar}")).expect("Should write");
+
+    let args: Vec<String> = vec!["mcmodgetter", "update", "-mcv", "1.21.8", "--retries", "0", "--modrinth-url", modrinth.base_url()]
+        .into_iter()
+        .map(String::from)
+        .collect();
+    let conf = arguments::Config::build_from_args(&args).expect("Should parse");
+    let client = create_client().expect("Client should be created");
+    let err = update_mods(&conf, &client, &out_dir).await.expect_err("Update should fail");
+    assert!(matches!(err.downcast_ref::<UpdateError>(), Some(UpdateError::Incomplete(1, 1))));
+    assert!(out_dir.join(old_jar).exists());
+    assert!(!out_dir.join(new_jar).exists());
+    assert!(!staging::previous_dir(&out_dir).exists());
+    assert_eq!(staging_leftovers(&out_dir), 0);
+    fs::remove_dir_all(&out_dir).expect("Should remove");
+}
+
+#[test]
+fn format_table_pads_columns() {
+    let rows = vec![vec![String::from("Sodium"), String::from("0.7.2"), String::from("0.7.3")]];
+    let table = installed::format_table(&["Mod", "Before", "After"], &rows);
+    assert_eq!(table, "Mod     Before  After\n------  ------  -----\nSodium  0.7.2   0.7.3");
+}
+
+#[tokio::test]
+async fn outdated_reports_newer_versions() {
+    let old_jar = "sodium-fabric-0.7.2+mc1.21.8.jar";
+    let current_jar = "maxed-1.0.jar";
+    // A build published after the newest listed one isn't outdated either
+    let ahead_jar = "sodium-ahead.jar";
+    let mut ahead: serde_json::Value = serde_json::from_str(&fixture("version_7pwil2dy.json")).expect("Fixture should be JSON");
+    ahead["id"] = "aheadXYZ".into();
+    ahead["date_published"] = "2025-11-01T10:00:00.000000Z".into();
+    let hash_map = format!(
+        r#"{{"{}":{},"{}":{},"{}":{}}}"#,
+        jar_sha512(old_jar), fixture("version_u1OBWrvJ.json"),
+        jar_sha512(current_jar), fixture("version_7pwil2dy.json"),
+        jar_sha512(ahead_jar), ahead
+    );
+    let modrinth = modrinth_fixtures_with(vec![
+        (String::from("/v2/version_files"), 200, hash_map.into_bytes())
+    ]).await;
+    let out_dir = temp_out_dir("outdated");
+    fs::write(out_dir.join(old_jar), format!("jar:{old_jar}")).expect("Should write");
+    fs::write(out_dir.join(current_jar), format!("jar:{current_jar}")).expect("Should write");
+    fs::write(out_dir.join(ahead_jar), format!("jar:{ahead_jar}")).expect("Should write");
+    let query = VersionQuery::build_query("1.21.8", "fabric");
+
+    let jars = installed::identify(&modrinth, installed::list_jars(&out_dir).expect("Should list"))
+        .await
+        .expect("Should identify");
+    let mut outdated = Vec::new();
+    for (jar, version) in &jars {
+        let version = version.as_ref().expect("All jars are known");
+        if let Some(o) = installed::check_outdated(&modrinth, &query, jar, version).await.expect("Should check") {
+            outdated.push(o);
+        }
+    }
+    assert_eq!(outdated.len(), 1);
+    assert_eq!(outdated[0].title(), "Sodium");
+    assert_eq!(outdated[0].installed(), "mc1.21.8-0.7.2-fabric");
+    assert_eq!(outdated[0].available(), "mc1.21.8-0.7.3-fabric");
+    assert_eq!(outdated[0].version_type(), "release");
+    let json: serde_json::Value = serde_json::to_value(&outdated).expect("Should serialize");
+    assert_eq!(json[0]["filename"], old_jar);
+    fs::remove_dir_all(&out_dir).expect("Should remove");
+}
+
+#[tokio::test]
+async fn scan_identifies_and_flags_unmanaged() {
+    let known_jar = "sodium-fabric-0.7.3+mc1.21.8.jar";
+    let hash_map = format!(r#"{{"{}":{}}}"#, jar_sha512(known_jar), fixture("version_7pwil2dy.json"));
+    let modrinth = modrinth_fixtures_with(vec![
+        (String::from("/v2/version_files"), 200, hash_map.into_bytes())
+    ]).await;
+    let out_dir = temp_out_dir("scan");
+    fs::write(out_dir.join(known_jar), format!("jar:{known_jar}")).expect("Should write");
+    fs::write(out_dir.join("dropped-in.jar"), "by hand").expect("Should write");
+    fs::write(out_dir.join("notes.txt"), "not a jar").expect("Should write");
+
+    let jars = installed::identify(&modrinth, installed::list_jars(&out_dir).expect("Should list"))
+        .await
+        .expect("Should identify");
+    assert_eq!(jars.len(), 2);
+    assert_eq!(jars[0].0.filename(), "dropped-in.jar");
+    assert!(jars[0].1.is_none());
+    assert_eq!(jars[1].1.as_ref().expect("Should be known").id(), "7pwil2dy");
+    let titles = installed::project_titles(&modrinth, &jars).await;
+    assert_eq!(titles["AANobbMI"], "Sodium");
+    fs::remove_dir_all(&out_dir).expect("Should remove");
+}
+
+#[tokio::test]
+async fn import_dir_writes_parseable_manifest() {
+    let known_jar = "sodium-fabric-0.7.3+mc1.21.8.jar";
+    let older_jar = "sodium-fabric-0.7.2+mc1.21.8.jar";
+    let hash_map = format!(
+        r#"{{"{}":{},"{}":{}}}"#,
+        jar_sha512(known_jar), fixture("version_7pwil2dy.json"),
+        jar_sha512(older_jar), fixture("version_u1OBWrvJ.json")
+    );
+    let modrinth = modrinth_fixtures_with(vec![
+        (String::from("/v2/version_files"), 200, hash_map.into_bytes())
+    ]).await;
+    let dir = temp_out_dir("import_dir");
+    let out_dir = dir.join("mods");
+    fs::create_dir_all(&out_dir).expect("Should create");
+    fs::write(out_dir.join(known_jar), format!("jar:{known_jar}")).expect("Should write");
+    fs::write(out_dir.join(older_jar), format!("jar:{older_jar}")).expect("Should write");
+    fs::write(out_dir.join("dropped-in.jar"), "by hand").expect("Should write");
+    let manifest = dir.join("mods.txt");
+
+    let args: Vec<String> = vec!["mcmodgetter", "import-dir", "mods.txt", "--modrinth-url", modrinth.base_url()]
+        .into_iter()
+        .map(String::from)
+        .collect();
+    let conf = arguments::Config::build_from_args(&args).expect("Should parse");
+    let client = create_client().expect("Client should be created");
+    import_dir(&conf, &client, &manifest, &out_dir).await.expect("Should import");
+
+    let text = fs::read_to_string(&manifest).expect("Should exist");
+    assert!(text.contains("# Sodium\nAANobbMI\n"));
+    assert!(text.contains("# dropped-in.jar (not found on Modrinth)"));
+    let ids = file_parse::parse_ids(&manifest).expect("Should parse");
+    let entries = ids.modrinth().as_ref().expect("Should have ids");
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].id(), "AANobbMI");
+    assert!(ids.curseforge().is_none());
+    assert!(import_dir(&conf, &client, &manifest, &out_dir).await.is_err());
+    fs::remove_dir_all(&dir).expect("Should remove");
+}
+
+#[test]
+fn normalize_slugs_and_urls() {
+    let cases = [
+        ("AANobbMI", ("AANobbMI", None)),
+        ("sodium", ("sodium", None)),
+        ("https://modrinth.com/mod/sodium", ("sodium", None)),
+        ("modrinth.com/project/sodium/", ("sodium", None)),
+        ("https://modrinth.com/mod/sodium/version/u1OBWrvJ", ("sodium", Some("u1OBWrvJ"))),
+        ("https://www.modrinth.com/shader/complementary?tab=versions", ("complementary", None)),
+    ];
+    for (input, (project, version)) in cases {
+        let (p, v) = file_parse::normalize_modrinth_id(input).expect("Should normalize");
+        assert_eq!(p, project, "{input}");
+        assert_eq!(v.as_deref(), version, "{input}");
+    }
+    assert!(file_parse::normalize_modrinth_id("https://example.com/mod/sodium").is_err());
+    assert!(file_parse::normalize_modrinth_id("https://modrinth.com/user/someone").is_err());
+
+    let entry = file_parse::parse_input_line("https://modrinth.com/mod/sodium/version/u1OBWrvJ # pinned")
+        .expect("should parse")
+        .expect("should be some");
+    assert_eq!(entry.id(), "sodium");
+    assert_eq!(entry.version_id().as_deref(), Some("u1OBWrvJ"));
+    assert!(file_parse::parse_input_line("https://modrinth.com/mod/sodium/version/u1OBWrvJ version=7pwil2dy").is_err());
+}
+
+#[tokio::test]
+async fn build_mod_from_slug_and_version_url() {
+    let modrinth = modrinth_fixtures_with(vec![
+        (String::from("/v2/project/sodium"), 200, fixture("project_AANobbMI.json").into_bytes()),
+        (String::from("/v2/project/sodium/version/mc1.21.8-0.7.2-fabric"), 200, fixture("version_u1OBWrvJ.json").into_bytes()),
+    ]).await;
+    let query = VersionQuery::build_query("1.21.8", "fabric");
+
+    let slug = file_parse::entry_from_id("sodium", None).expect("Should normalize");
+    let m = Mod::build_from_entry(&modrinth, &slug, &query).await.expect("Should resolve");
+    assert_eq!(m.project_id(), "AANobbMI");
+    assert_eq!(m.version_id(), "7pwil2dy");
+
+    let url = file_parse::entry_from_id("https://modrinth.com/mod/sodium/version/mc1.21.8-0.7.2-fabric", None)
+        .expect("Should normalize");
+    let m = Mod::build_from_entry(&modrinth, &url, &query).await.expect("Should resolve");
+    assert_eq!(m.project_id(), "AANobbMI");
+    assert_eq!(m.version_id(), "u1OBWrvJ");
+}
+
+#[tokio::test]
+async fn pinned_entries_resolve_dependencies() {
+    let modrinth = modrinth_fixtures().await;
+    let args: Vec<String> = vec!["mcmodgetter", "--readfile", "mods.txt", "-mcv", "1.21.8"]
+        .into_iter()
+        .map(String::from)
+        .collect();
+    let conf = arguments::Config::build_from_args(&args).expect("Should parse");
+    let entries = vec![
+        file_parse::parse_input_line("89Wsn8GD@1.5.9+1.21.6-fabric").expect("should parse").expect("should be some"),
+        file_parse::parse_input_line("AANobbMI@u1OBWrvJ").expect("should parse").expect("should be some"),
+    ];
+    let resolution = resolve_list(&conf, &modrinth, &entries).await;
+    let mods = resolution.mods();
+    assert_eq!(mods[0].version_id(), "GRuX8d2G");
+    assert_eq!(mods[1].version_id(), "u1OBWrvJ");
+    assert!(mods.iter().any(|m| m == &String::from("P7dR8mSH")));
+    assert!(mods.iter().any(|m| m == &String::from("Ha28R6CL")));
+    assert!(resolution.unresolved().is_empty());
+
+    let missing = vec![file_parse::parse_input_line("NoSuchId").expect("should parse").expect("should be some")];
+    let partial = resolve_list(&conf, &modrinth, &missing).await;
+    assert!(partial.mods().is_empty());
+    assert_eq!(partial.unresolved(), &vec![String::from("NoSuchId")]);
+
+    let version_only = resolve_version(&conf, &modrinth, "GRuX8d2G").await.expect("Should resolve");
+    assert_eq!(version_only.mods().len(), 3);
+}
+
+#[tokio::test]
+async fn dependency_graph_tree_and_why() {
+    let modrinth = modrinth_fixtures().await;
+    let args: Vec<String> = vec!["mcmodgetter", "--readfile", "mods.txt", "-mcv", "1.21.8", "why", "fabric api"]
+        .into_iter()
+        .map(String::from)
+        .collect();
+    let conf = arguments::Config::build_from_args(&args).expect("Should parse");
+    assert!(matches!(conf.report(), Some(arguments::Report::Why("fabric api"))));
+    let entries = vec![
+        file_parse::parse_input_line("89Wsn8GD").expect("should parse").expect("should be some"),
+        file_parse::parse_input_line("mOgUt4GM").expect("should parse").expect("should be some"),
+    ];
+    let resolution = resolve_list(&conf, &modrinth, &entries).await;
+    let graph = resolution.graph();
+    assert_eq!(graph.roots(), &vec![String::from("89Wsn8GD"), String::from("mOgUt4GM")]);
+    assert_eq!(
+        graph.format_tree(),
+        "Capes (89Wsn8GD)
+  Fabric API (P7dR8mSH)
+  Fabric Language Kotlin (Ha28R6CL)
+Mod Menu (mOgUt4GM)
+  Fabric API (P7dR8mSH)
+  Text Placeholder API (eBmGOhzz)"
+    );
+    let fabric_api = graph.find("fabric api").expect("Should find by title");
+    assert_eq!(graph.format_why(fabric_api), "Capes -> Fabric API\nMod Menu -> Fabric API");
+    assert!(graph.find("sodium").is_none());
+
+    let bad: Vec<String> = vec!["mcmodgetter", "--locked", "tree"].into_iter().map(String::from).collect();
+    assert!(arguments::Config::build_from_args(&bad).is_err());
+}
+
+#[tokio::test]
+async fn cached_resolver_matches_plain_with_fewer_requests() {
+    let modrinth = modrinth_fixtures().await;
+    let query = VersionQuery::build_query("1.21.8", "fabric");
+    let entries = vec![
+        file_parse::parse_input_line("89Wsn8GD").expect("should parse").expect("should be some"),
+        file_parse::parse_input_line("mOgUt4GM").expect("should parse").expect("should be some"),
+        file_parse::parse_input_line("P7dR8mSH").expect("should parse").expect("should be some"),
+    ];
+    let mut plain = collect_entries(&modrinth, &entries, &query).await;
+    resolve_dependencies(&modrinth, &query, &mut plain).await;
+
+    let cached = cache::CachedProvider::new(&modrinth);
+    let roots: Vec<String> = entries.iter().map(|e| e.id().clone()).collect();
+    cached.resolve_projects(&roots).await.expect("Should load");
+    let mut mods = collect_entries(&cached, &entries, &query).await;
+    let graph = resolve_dependencies(&cached, &query, &mut mods).await;
+    let ids = |mods: &[Mod]| mods.iter().map(|m| m.version_id().clone()).collect::<Vec<String>>();
+    assert_eq!(ids(&mods), ids(&plain));
+    assert_eq!(graph.format_why("P7dR8mSH").lines().count(), 3);
+
+    // Fabric API is needed by Capes and Mod Menu but resolved once, and
+    // projects come in two bulk requests: the roots, then new dependencies
+    let stats = cached.stats();
+    assert_eq!(stats.dependencies().requests(), 3);
+    assert_eq!(stats.dependencies().cached(), 1);
+    assert_eq!(stats.projects().requests(), 2);
+    assert_eq!(stats.requests(), 8);
+    assert!(stats.summary().starts_with("8 requests (2 projects, 3 version lists, 0 versions, 3 dependencies)"));
+}
+
+/// Capes pins an older Fabric API than Mod Menu does; only the older one
+/// needs FerriteCore
+async fn conflicting_pins_fixtures() -> Modrinth {
+    let with = |name: &str, edit: &dyn Fn(&mut serde_json::Value)| -> Vec<u8> {
+        let mut json: serde_json::Value = serde_json::from_str(&fixture(name)).expect("Should be JSON");
+        edit(&mut json);
+        serde_json::to_vec(&json).expect("Should serialize")
+    };
+    let pin = |version: &str| serde_json::json!(
+        {"version_id": version, "project_id": "P7dR8mSH", "file_name": null, "dependency_type": "required"}
+    );
+    modrinth_fixtures_with(vec![
+        (String::from("/v2/version/fapiOld1"), 200, with("version_KhCFoeip.json", &|v| {
+            v["id"] = "fapiOld1".into();
+            v["version_number"] = "0.130.0+1.21.8".into();
+            v["date_published"] = "2025-07-01T12:00:00.000000Z".into();
+            v["dependencies"] = serde_json::json!([
+                {"version_id": null, "project_id": "uXXizFIs", "file_name": null, "dependency_type": "required"}
+            ]);
+        })),
+        (String::from("/v2/project/89Wsn8GD/version/capesPin"), 200, with("version_GRuX8d2G.json", &|v| {
+            v["id"] = "capesPin".into();
+            v["dependencies"] = serde_json::json!([pin("fapiOld1")]);
+        })),
+        (String::from("/v2/project/mOgUt4GM/version/menuPin"), 200, with("version_s9EUkWvq.json", &|v| {
+            v["id"] = "menuPin".into();
+            v["dependencies"] = serde_json::json!([pin("KhCFoeip")]);
+        })),
+    ]).await
+}
+
+#[tokio::test]
+async fn conflicting_pins_fail_unless_newest_is_preferred() {
+    let modrinth = conflicting_pins_fixtures().await;
+    let entries = vec![
+        file_parse::parse_input_line("89Wsn8GD@capesPin").expect("should parse").expect("should be some"),
+        file_parse::parse_input_line("mOgUt4GM@menuPin").expect("should parse").expect("should be some"),
+    ];
+    let conf_with = |extra: &[&str]| -> Vec<String> {
+        ["mcmodgetter", "--readfile", "mods.txt", "-mcv", "1.21.8"].iter()
+            .chain(extra)
+            .map(|a| a.to_string())
+            .collect()
+    };
+
+    let args = conf_with(&[]);
+    let conf = arguments::Config::build_from_args(&args).expect("Should parse");
+    let resolution = resolve_list(&conf, &modrinth, &entries).await;
+    let conflict = &resolution.conflicts()[0];
+    assert_eq!(resolution.conflicts().len(), 1);
+    assert_eq!(conflict.selected(), "fapiOld1");
+    assert_eq!(
+        conflict.to_string(),
+        "Fabric API is pinned to different versions: 0.130.0+1.21.8 required by Capes [selected]; 0.133.4+1.21.8 required by Mod Menu"
+    );
+    assert!(matches!(check_resolution(&conf, &resolution), Err(ModError::VersionConflict(_))));
+    assert!(resolution.mods().iter().any(|m| m == &String::from("uXXizFIs")));
+
+    let args = conf_with(&["--prefer-newest"]);
+    let conf = arguments::Config::build_from_args(&args).expect("Should parse");
+    let resolution = resolve_list(&conf, &modrinth, &entries).await;
+    assert!(resolution.conflicts().is_empty());
+    let fabric_api = resolution.mods().iter().find(|m| *m == &String::from("P7dR8mSH")).expect("Should be in set");
+    assert_eq!(fabric_api.version_id(), "KhCFoeip");
+    // FerriteCore came only from the replaced Fabric API
+    assert!(!resolution.mods().iter().any(|m| m == &String::from("uXXizFIs")));
+    assert!(resolution.graph().children("P7dR8mSH").is_empty());
+    assert!(resolution.graph().format_why("uXXizFIs").is_empty());
+    assert!(check_resolution(&conf, &resolution).is_ok());
+}
+
+#[test]
+fn pin_version_from_cli() {
+    let parse = |args: &[&str]| -> Result<(), arguments::ArgError> {
+        let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        let conf = arguments::Config::build_from_args(&args)?;
+        match conf.mode() {
+            arguments::AppMode::SingleId(id) => {
+                let entry = file_parse::entry_from_id(id, *conf.version()).map_err(|_| "conflict")?;
+                assert_eq!(entry.version_id().as_deref(), Some("u1OBWrvJ"));
+            },
+            arguments::AppMode::SingleVersion(v) => assert_eq!(v, "u1OBWrvJ"),
+            _ => panic!("Unexpected mode")
+        }
+        Ok(())
+    };
+    parse(&["mcmodgetter", "-id", "sodium", "-version", "u1OBWrvJ", "-mcv", "1.21.8"]).expect("Should pin");
+    parse(&["mcmodgetter", "-id", "sodium@u1OBWrvJ", "-mcv", "1.21.8"]).expect("Should pin");
+    parse(&["mcmodgetter", "-version", "u1OBWrvJ", "-mcv", "1.21.8"]).expect("Should pin");
+    assert!(parse(&["mcmodgetter", "-id", "sodium@u1OBWrvJ", "-version", "7pwil2dy", "-mcv", "1.21.8"]).is_err());
+    assert!(parse(&["mcmodgetter", "--locked", "-version", "u1OBWrvJ"]).is_err());
+}
+
+#[test]
+fn locked_is_a_flag() {
+    let parse = |args: &[&str]| {
+        let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        arguments::Config::build_from_args(&args).map(|conf| matches!(conf.mode(), arguments::AppMode::Locked))
+    };
+    assert!(parse(&["mcmodgetter", "--locked"]).expect("Should parse"));
+    assert!(parse(&["mcmodgetter", "--locked", "--readfile", "mods.txt"]).expect("Should parse"));
+    assert!(parse(&["mcmodgetter", "--readfile", "mods.txt", "--locked"]).expect("Should parse"));
+    assert!(matches!(
+        parse(&["mcmodgetter", "--locked", "-id", "sodium", "-mcv", "1.21.8"]),
+        Err(arguments::ArgError::LockedWith("-id"))
+    ));
+    assert!(matches!(
+        parse(&["mcmodgetter", "--locked", "update", "-mcv", "1.21.8"]),
+        Err(arguments::ArgError::LockedWith("update"))
+    ));
+}
+
+#[tokio::test]
+async fn optional_dependencies_reported_and_opt_in() {
+    let modrinth = modrinth_fixtures().await;
+    let query = VersionQuery::build_query("1.21.8", "fabric");
+    let mut mods = collect_mods(&modrinth, &[String::from("AANobbMI")], &query).await;
+    resolve_dependencies(&modrinth, &query, &mut mods).await;
+    assert_eq!(mods.len(), 1);
+    let suggested = optional_dependencies(&modrinth, &mods).await;
+    assert_eq!(suggested.len(), 1);
+    assert_eq!(suggested[0].0, "Sodium");
+    assert_eq!(suggested[0].1.get_title(), "Fabric API");
+
+    let optional = arguments::OptionalDeps::Projects(vec![String::from("P7dR8mSH")]);
+    resolve_dependencies_with(&modrinth, &query, &mut mods, &optional).await;
+    assert_eq!(mods.len(), 2);
+    assert!(optional_dependencies(&modrinth, &mods).await.is_empty());
+
+    let entry = file_parse::parse_input_line("AANobbMI optional=true").expect("should parse").expect("should be some");
+    let mut mods = collect_entries(&modrinth, &[entry], &query).await;
+    resolve_dependencies(&modrinth, &query, &mut mods).await;
+    assert!(mods.iter().any(|m| m == &String::from("P7dR8mSH")));
+}
+
+#[tokio::test]
+async fn incompatible_mods_are_refused() {
+    let mut sodium: serde_json::Value = serde_json::from_str(&fixture("versions_AANobbMI.json")).expect("Should parse");
+    sodium[0]["dependencies"] = serde_json::json!([
+        {"project_id": "uXXizFIs", "version_id": null, "dependency_type": "incompatible"},
+        {"project_id": null, "version_id": null, "dependency_type": "incompatible"}
+    ]);
+    let modrinth = modrinth_fixtures_with(vec![
+        (String::from("/v2/project/AANobbMI/version"), 200, sodium.to_string().into_bytes())
+    ]).await;
+    let query = VersionQuery::build_query("1.21.8", "fabric");
+    let mut mods = collect_mods(&modrinth, &[String::from("AANobbMI"), String::from("uXXizFIs")], &query).await;
+    resolve_dependencies(&modrinth, &query, &mut mods).await;
+
+    let found = find_incompatibilities(&mods);
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].declared_by(), "Sodium");
+    assert_eq!(found[0].conflicts_with(), "FerriteCore");
+    assert!(found[0].to_string().contains("Sodium 0.7.3"));
+
+    let args = |extra: &[&str]| -> Vec<String> {
+        ["mcmodgetter", "--readfile", "mods.txt", "-mcv", "1.21.8"].iter()
+            .chain(extra)
+            .map(|a| a.to_string())
+            .collect()
+    };
+    let strict = args(&[]);
+    let conf = arguments::Config::build_from_args(&strict).expect("Should parse");
+    assert!(matches!(check_compatibility(&conf, &mods), Err(ModError::Incompatible(_))));
+    let lenient = args(&["--allow-incompatible"]);
+    let conf = arguments::Config::build_from_args(&lenient).expect("Should parse");
+    assert!(check_compatibility(&conf, &mods).is_ok());
+    assert!(find_incompatibilities(&mods[..1]).is_empty());
+}
+
+#[tokio::test]
+async fn http_layer_retries_temporary_failures() {
+    let client = create_client().expect("Client should be created");
+    let (url, hits) = scripted_server(vec![
+        (503, vec![], String::from("{}")),
+        (429, vec![("X-Ratelimit-Remaining", "0"), ("X-Ratelimit-Reset", "0")], String::from("{}")),
+        (200, vec![], fixture("project_AANobbMI.json")),
+    ]).await;
+    let modrinth = Modrinth::build(&client, &url).with_retries(3, std::time::Duration::from_millis(1));
+    let project = get_project(&modrinth, "AANobbMI").await.expect("Should succeed on the third try");
+    assert_eq!(project.get_title(), "Sodium");
+    assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 3);
+
+    let (url, hits) = scripted_server(vec![(500, vec![], String::from("{}"))]).await;
+    let modrinth = Modrinth::build(&client, &url).with_retries(2, std::time::Duration::from_millis(1));
+    let err = get_project(&modrinth, "AANobbMI").await.err().expect("Should give up");
+    assert!(matches!(err, ModError::ServerError(500, _, _)));
+    assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 3);
+
+    let (url, hits) = scripted_server(vec![(429, vec![], String::from("{}"))]).await;
+    let modrinth = Modrinth::build(&client, &url).with_retries(1, std::time::Duration::from_millis(1));
+    let err = get_project(&modrinth, "AANobbMI").await.err().expect("Should give up");
+    assert!(matches!(err, ModError::RateLimited(_, _)));
+    assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn http_layer_does_not_retry_client_errors() {
+    let client = create_client().expect("Client should be created");
+    let (url, hits) = scripted_server(vec![(404, vec![], String::from(r#"{"error":"not_found"}"#))]).await;
+    let modrinth = Modrinth::build(&client, &url).with_retries(3, std::time::Duration::from_millis(1));
+    let err = get_project(&modrinth, "nope").await.err().expect("Should fail");
+    assert!(matches!(err, ModError::NotFound(ref id, ref u) if id == "nope" && u.ends_with("/v2/project/nope")));
+    assert!(err.hint().is_some());
+    assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+    let (url, _) = scripted_server(vec![(401, vec![], String::from("{}"))]).await;
+    let modrinth = Modrinth::build(&client, &url);
+    let err = get_version_from_version_id(&modrinth, "hidden1").await.err().expect("Should fail");
+    assert!(matches!(err, ModError::Unauthorized(ref id, _) if id == "hidden1"));
+
+    // Unknown routes on the fixture API are 404s
+    let modrinth = modrinth_fixtures().await;
+    let err = get_project(&modrinth, "notAProj").await.err().expect("Should fail");
+    assert!(matches!(err, ModError::NotFound(ref id, _) if id == "notAProj"));
+    let query = VersionQuery::build_query("1.21.8", "fabric");
+    let entries = vec![
+        file_parse::parse_input_line("notAProj").expect("should parse").expect("should be some"),
+        file_parse::parse_input_line("AANobbMI").expect("should parse").expect("should be some"),
+    ];
+    let mods = collect_entries(&modrinth, &entries, &query).await;
+    assert_eq!(mods.len(), 1);
+}
+
+#[tokio::test]
+async fn http_layer_waits_for_rate_limit_reset() {
+    let client = create_client().expect("Client should be created");
+    let (url, hits) = scripted_server(vec![
+        (200, vec![("X-Ratelimit-Remaining", "0"), ("X-Ratelimit-Reset", "1")], fixture("project_AANobbMI.json")),
+    ]).await;
+    let modrinth = Modrinth::build(&client, &url);
+    get_project(&modrinth, "AANobbMI").await.expect("Should load");
+    let start = std::time::Instant::now();
+    get_project(&modrinth, "AANobbMI").await.expect("Should load");
+    assert!(start.elapsed() >= std::time::Duration::from_millis(900));
+    assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 2);
+
+    // Concurrent callers all wait for the spent window, not just the first
+    let start = std::time::Instant::now();
+    let timed = || async {
+        get_project(&modrinth, "AANobbMI").await.expect("Should load");
+        start.elapsed()
+    };
+    let (a, b) = tokio::join!(timed(), timed());
+    assert!(a >= std::time::Duration::from_millis(900));
+    assert!(b >= std::time::Duration::from_millis(900));
+    assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 4);
+}
+
+#[tokio::test]
+async fn http_layer_ignores_rate_limit_reset_for_server_errors() {
+    let client = create_client().expect("Client should be created");
+    let (url, hits) = scripted_server(vec![
+        (500, vec![("X-Ratelimit-Remaining", "250"), ("X-Ratelimit-Reset", "60")], String::from("{}")),
+        (200, vec![], fixture("project_AANobbMI.json")),
+    ]).await;
+    let modrinth = Modrinth::build(&client, &url).with_retries(1, std::time::Duration::from_millis(1));
+    let start = std::time::Instant::now();
+    get_project(&modrinth, "AANobbMI").await.expect("Should load after one retry");
+    assert!(start.elapsed() < std::time::Duration::from_secs(5));
+    assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 2);
+}